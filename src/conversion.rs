@@ -0,0 +1,213 @@
+use std::fmt;
+
+use crate::{lox_callable::NativeError, token::Literal};
+
+/// The error returned when a Lox value doesn't have the type the host asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl ConversionError {
+    fn new(expected: &'static str, value: &Literal) -> Self {
+        Self {
+            expected,
+            found: value.type_name(),
+        }
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Expected {} but got {}.", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl From<ConversionError> for NativeError {
+    fn from(error: ConversionError) -> Self {
+        Self {
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Converts a Rust value into a Lox value.
+pub trait IntoLox {
+    fn into_lox(self) -> Literal;
+}
+
+/// Converts a Lox value into a Rust value, failing if the value has the wrong type.
+pub trait FromLox: Sized {
+    fn from_lox(value: &Literal) -> Result<Self, ConversionError>;
+}
+
+impl IntoLox for Literal {
+    fn into_lox(self) -> Literal {
+        self
+    }
+}
+
+impl FromLox for Literal {
+    fn from_lox(value: &Literal) -> Result<Self, ConversionError> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoLox for f64 {
+    fn into_lox(self) -> Literal {
+        Literal::Float(self)
+    }
+}
+
+impl FromLox for f64 {
+    fn from_lox(value: &Literal) -> Result<Self, ConversionError> {
+        match value {
+            Literal::Float(number) => Ok(*number),
+            _ => Err(ConversionError::new("number", value)),
+        }
+    }
+}
+
+impl IntoLox for f32 {
+    fn into_lox(self) -> Literal {
+        Literal::Float(self as f64)
+    }
+}
+
+impl FromLox for f32 {
+    fn from_lox(value: &Literal) -> Result<Self, ConversionError> {
+        f64::from_lox(value).map(|number| number as f32)
+    }
+}
+
+// Lox only has doubles, so an integer converts back only if the number is
+// whole and fits in the target type.
+macro_rules! integer_conversions {
+    ($($int:ty),*) => {
+        $(
+            impl IntoLox for $int {
+                fn into_lox(self) -> Literal {
+                    Literal::Float(self as f64)
+                }
+            }
+
+            impl FromLox for $int {
+                fn from_lox(value: &Literal) -> Result<Self, ConversionError> {
+                    let number = f64::from_lox(value)?;
+                    if number.fract() == 0.0
+                        && number >= <$int>::MIN as f64
+                        // `MAX as f64` rounds up to a power of two for 64-bit
+                        // types, which `MAX + 1` also is, so the bound is exact.
+                        && number < <$int>::MAX as f64 + 1.0
+                    {
+                        Ok(number as $int)
+                    } else {
+                        Err(ConversionError::new(
+                            concat!("an integer that fits in ", stringify!($int)),
+                            value,
+                        ))
+                    }
+                }
+            }
+        )*
+    };
+}
+
+integer_conversions!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl IntoLox for bool {
+    fn into_lox(self) -> Literal {
+        Literal::BoolLiteral(self)
+    }
+}
+
+impl FromLox for bool {
+    fn from_lox(value: &Literal) -> Result<Self, ConversionError> {
+        match value {
+            Literal::BoolLiteral(bool_val) => Ok(*bool_val),
+            _ => Err(ConversionError::new("boolean", value)),
+        }
+    }
+}
+
+impl IntoLox for String {
+    fn into_lox(self) -> Literal {
+        Literal::StringLiteral(self)
+    }
+}
+
+impl IntoLox for &str {
+    fn into_lox(self) -> Literal {
+        Literal::StringLiteral(self.to_string())
+    }
+}
+
+impl FromLox for String {
+    fn from_lox(value: &Literal) -> Result<Self, ConversionError> {
+        match value {
+            Literal::StringLiteral(string) => Ok(string.clone()),
+            _ => Err(ConversionError::new("string", value)),
+        }
+    }
+}
+
+impl<T: IntoLox> IntoLox for Option<T> {
+    fn into_lox(self) -> Literal {
+        match self {
+            Some(value) => value.into_lox(),
+            None => Literal::NoneLiteral,
+        }
+    }
+}
+
+impl<T: FromLox> FromLox for Option<T> {
+    fn from_lox(value: &Literal) -> Result<Self, ConversionError> {
+        match value {
+            Literal::NoneLiteral => Ok(None),
+            _ => T::from_lox(value).map(Some),
+        }
+    }
+}
+
+impl IntoLox for () {
+    fn into_lox(self) -> Literal {
+        Literal::NoneLiteral
+    }
+}
+
+impl FromLox for () {
+    fn from_lox(value: &Literal) -> Result<Self, ConversionError> {
+        match value {
+            Literal::NoneLiteral => Ok(()),
+            _ => Err(ConversionError::new("nil", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers_reject_values_just_past_their_range() {
+        let two_pow_63 = 2f64.powi(63);
+        assert!(i64::from_lox(&Literal::Float(two_pow_63)).is_err());
+        assert_eq!(i64::from_lox(&Literal::Float(-two_pow_63)), Ok(i64::MIN));
+        assert!(u64::from_lox(&Literal::Float(2f64.powi(64))).is_err());
+        assert!(usize::from_lox(&Literal::Float(2f64.powi(64))).is_err());
+        assert_eq!(i32::from_lox(&Literal::Float(2147483647.0)), Ok(i32::MAX));
+        assert!(i32::from_lox(&Literal::Float(2147483648.0)).is_err());
+        assert_eq!(u8::from_lox(&Literal::Float(255.0)), Ok(255));
+        assert!(u8::from_lox(&Literal::Float(-1.0)).is_err());
+    }
+
+    #[test]
+    fn integers_reject_fractions() {
+        assert!(i32::from_lox(&Literal::Float(1.5)).is_err());
+        assert!(i64::from_lox(&Literal::Float(f64::NAN)).is_err());
+        assert!(i64::from_lox(&Literal::Float(f64::INFINITY)).is_err());
+    }
+}
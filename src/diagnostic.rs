@@ -0,0 +1,183 @@
+use std::fmt;
+
+use crate::{
+    error_code::ErrorCode, interpreter::RuntimeErrorOrReturn, span::Span, token::Token,
+    token_type::TokenType,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// The stage of a run that produced a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Scan,
+    Parse,
+    Resolve,
+    Runtime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    /// Only the line is known, as for scanner errors.
+    Line,
+    /// The end of the input.
+    End,
+    /// The token with this lexeme.
+    Token(String),
+    /// Nowhere in a script: the host called into the engine, for example
+    /// with the wrong number of arguments.
+    Host,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub phase: Phase,
+    pub code: ErrorCode,
+    pub message: String,
+    pub line: usize,
+    pub location: Location,
+    /// The exact source range, when the diagnostic points at one.
+    pub span: Option<Span>,
+    /// Other places in the source that explain the diagnostic.
+    pub labels: Vec<Label>,
+    /// The calls a runtime error happened in, innermost first.
+    pub trace: Vec<Frame>,
+    /// A suggestion for fixing the error, such as a similar name. The short
+    /// format leaves it out, as jlox has none.
+    pub help: Option<String>,
+}
+
+/// A call that was in progress when a runtime error happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// The function, `Class.method` or `Class.init` that was called.
+    pub function: String,
+    /// Where it was called from; `None` when the host made the call.
+    pub call_site: Option<Span>,
+    pub line: usize,
+}
+
+/// A secondary span attached to a diagnostic, such as an earlier declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn error(
+        phase: Phase,
+        code: ErrorCode,
+        line: usize,
+        location: Location,
+        message: &str,
+    ) -> Self {
+        Self {
+            severity: Severity::Error,
+            phase,
+            code,
+            message: message.to_string(),
+            line,
+            location,
+            span: None,
+            labels: Vec::new(),
+            trace: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub(crate) fn with_label(mut self, span: Span, message: &str) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.to_string(),
+        });
+        self
+    }
+
+    pub(crate) fn error_at_token(
+        phase: Phase,
+        code: ErrorCode,
+        token: &Token,
+        message: &str,
+    ) -> Self {
+        // Tokens made up for host calls are on line 0 and aren't in any source.
+        if token.span.line == 0 {
+            return Self::error(phase, code, 0, Location::Host, message);
+        }
+        let location = if token.token_type == TokenType::Eof {
+            Location::End
+        } else {
+            Location::Token(token.lexeme.clone())
+        };
+        Self::error(phase, code, token.span.line as usize, location, message).with_span(token.span)
+    }
+
+    pub(crate) fn from_runtime_error(error: &RuntimeErrorOrReturn) -> Self {
+        let code = error.code.expect("returns are not errors");
+        let mut diagnostic =
+            Self::error_at_token(Phase::Runtime, code, &error.token, &error.message);
+        diagnostic.help = error.help.as_deref().map(String::from);
+        diagnostic
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// Formats the diagnostic the way jlox reports it.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.location == Location::Host {
+            return write!(f, "{}\n[called by the host]", self.message);
+        }
+        if self.phase == Phase::Runtime {
+            return write!(f, "{}\n[line {}]", self.message, self.line);
+        }
+        let severity = match self.severity {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+        };
+        let location = match &self.location {
+            Location::Line => String::new(),
+            Location::End => " at end".to_string(),
+            Location::Token(lexeme) => format!(" at '{}'", lexeme),
+            Location::Host => unreachable!(),
+        };
+        write!(
+            f,
+            "[line {}] {}{}: {}",
+            self.line, severity, location, self.message
+        )
+    }
+}
+
+/// Collects the diagnostics of a single run.
+#[derive(Default)]
+pub(crate) struct Diagnostics {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub(crate) fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub(crate) fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+
+    pub(crate) fn into_vec(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
@@ -1,5 +1,9 @@
 use crate::{
+    error_code::ErrorCode,
     interpreter::{ExprVisitorResult, RuntimeErrorOrReturn},
+    limits::Charge,
+    lox_callable::LoxCallable,
+    suggest,
     token::{Literal, Token},
 };
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
@@ -7,11 +11,43 @@ use std::{cell::RefCell, collections::HashMap, rc::Rc};
 pub(crate) struct EnvironmentNode {
     pub(crate) environment: HashMap<String, Literal>,
     pub(crate) parent: Option<Rc<RefCell<EnvironmentNode>>>,
+    /// What the node and its bindings count against the memory limit.
+    pub(crate) charge: Charge,
 }
 
 impl EnvironmentNode {
     pub(crate) fn define(node: Rc<RefCell<EnvironmentNode>>, key: String, value: Literal) {
-        (*node).borrow_mut().environment.insert(key, value);
+        Self::insert(&node, key, value);
+    }
+
+    /// Binds `key` in `node`, and returns the value it was bound to before.
+    pub(crate) fn insert(
+        node: &Rc<RefCell<EnvironmentNode>>,
+        key: String,
+        value: Literal,
+    ) -> Option<Literal> {
+        let value = match value {
+            Literal::Callable(LoxCallable::UserFunction(function)) => {
+                Literal::Callable(LoxCallable::UserFunction(function.stored_in(node)))
+            }
+            value => value,
+        };
+        node.borrow_mut().environment.insert(key, value)
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<Literal> {
+        self.environment.get(key).map(loaded)
+    }
+}
+
+/// A value read back from an environment, with functions holding their
+/// closure strongly again.
+fn loaded(value: &Literal) -> Literal {
+    match value {
+        Literal::Callable(LoxCallable::UserFunction(function)) => {
+            Literal::Callable(LoxCallable::UserFunction(function.loaded()))
+        }
+        value => value.clone(),
     }
 }
 
@@ -31,12 +67,24 @@ impl EnvironmentTree {
         }))
     }
 
-    pub(crate) fn define_current(&self, name: String, value: Literal) {
-        self.current.borrow_mut().environment.insert(name, value);
+    /// Returns the value `name` was bound to before, if any.
+    pub(crate) fn define_current(&self, name: String, value: Literal) -> Option<Literal> {
+        EnvironmentNode::insert(&self.current, name, value)
     }
 
     pub(crate) fn define_global(&mut self, name: String, value: Literal) {
-        self.current.borrow_mut().environment.insert(name, value);
+        EnvironmentNode::insert(&self.root, name, value);
+    }
+
+    pub(crate) fn globals(&self) -> Vec<(String, Literal)> {
+        let mut globals: Vec<_> = (*self.root)
+            .borrow()
+            .environment
+            .iter()
+            .map(|(name, value)| (name.clone(), loaded(value)))
+            .collect();
+        globals.sort_by(|a, b| a.0.cmp(&b.0));
+        globals
     }
 
     pub(crate) fn get(&self, token: &Token, expr_id: usize) -> ExprVisitorResult {
@@ -44,30 +92,28 @@ impl EnvironmentTree {
             return self.get_at(&token.lexeme, *steps);
         }
 
-        if let Some(value) = (*self.root).borrow().environment.get(&token.lexeme) {
-            return Ok(value.clone());
-        }
-
-        return Err(RuntimeErrorOrReturn {
-            message: format!("Undefined variable '{}'.", token.lexeme),
-            token: token.clone(),
-            return_flag: false,
-        });
+        self.get_global(token)
     }
 
     pub(crate) fn get_at(&self, key: &str, steps: usize) -> ExprVisitorResult {
+        Ok(self.ancestor(steps).borrow().get(key).unwrap())
+    }
+
+    fn ancestor(&self, steps: usize) -> Rc<RefCell<EnvironmentNode>> {
         let mut current = self.current.clone();
         for _ in 0..steps {
             let tmp = (*current).borrow().parent.clone().unwrap();
             current = tmp;
         }
-        return Ok(current
-            .as_ref()
-            .borrow()
-            .environment
-            .get(key)
-            .unwrap()
-            .clone());
+        current
+    }
+
+    /// The environment the variable `expr_id` refers to lives in.
+    pub(crate) fn node_for(&self, expr_id: usize) -> Rc<RefCell<EnvironmentNode>> {
+        match self.id_steps_map.get(&expr_id) {
+            Some(steps) => self.ancestor(*steps),
+            None => self.root.clone(),
+        }
     }
 
     pub(crate) fn assign(
@@ -75,47 +121,56 @@ impl EnvironmentTree {
         token: &Token,
         value: Literal,
         expr_id: usize,
-    ) -> Result<(), RuntimeErrorOrReturn> {
-        if let Some(steps) = self.id_steps_map.get(&expr_id) {
-            let mut current = self.current.clone();
-            for _ in 0..*steps {
-                let tmp = (*current).borrow().parent.clone().unwrap();
-                current = tmp;
-            }
-            let env = &mut current.borrow_mut().environment;
-            env.insert(token.lexeme.clone(), value);
-            return Ok(());
+    ) -> ExprVisitorResult {
+        if !self.id_steps_map.contains_key(&expr_id)
+            && !self.root.borrow().environment.contains_key(&token.lexeme)
+        {
+            return Err(self.undefined_variable(token));
         }
-        let env = &mut self.root.borrow_mut().environment;
-        if !env.contains_key(&token.lexeme) {
-            return Err(RuntimeErrorOrReturn {
-                message: format!("Undefined variable '{}'.", token.lexeme),
-                token: token.clone(),
-                return_flag: false,
-            });
-        } else {
-            env.insert(token.lexeme.clone(), value);
-            return Ok(());
+        let node = self.node_for(expr_id);
+        let previous = EnvironmentNode::insert(&node, token.lexeme.clone(), value);
+        Ok(previous.unwrap_or(Literal::NoneLiteral))
+    }
+
+    /// Suggests a similar name from every environment visible from the current one.
+    fn undefined_variable(&self, token: &Token) -> RuntimeErrorOrReturn {
+        let mut names = Vec::new();
+        let mut node = Some(self.current.clone());
+        while let Some(current) = node {
+            let current = current.borrow();
+            names.extend(current.environment.keys().cloned());
+            node = current.parent.clone();
+        }
+        RuntimeErrorOrReturn {
+            message: format!("Undefined variable '{}'.", token.lexeme).into(),
+            code: Some(ErrorCode::UndefinedVariable),
+            token: token.clone(),
+            return_flag: false,
+            help: suggest::did_you_mean(&token.lexeme, names.iter().map(String::as_str)),
         }
     }
 
     pub(crate) fn get_current(&self) -> Rc<RefCell<EnvironmentNode>> {
-        return self.current.clone();
+        self.current.clone()
     }
 
     pub(crate) fn set_current(&mut self, current: Rc<RefCell<EnvironmentNode>>) {
         self.current = current;
     }
 
-    // pub(crate) fn get_global(&self) -> Rc<RefCell<EnvironmentNode>> {
-    //     return self.root.clone();
-    // }
+    pub(crate) fn get_global(&self, token: &Token) -> ExprVisitorResult {
+        if let Some(value) = (*self.root).borrow().get(&token.lexeme) {
+            return Ok(value);
+        }
+
+        Err(self.undefined_variable(token))
+    }
 
     pub(crate) fn set_step_for_id(&mut self, id: usize, steps: usize) {
         self.id_steps_map.insert(id, steps);
     }
 
-    pub(crate) fn get_step_for_id(&self, id: usize) -> Option<&usize>{
+    pub(crate) fn get_step_for_id(&self, id: usize) -> Option<&usize> {
         self.id_steps_map.get(&id)
     }
 
@@ -125,6 +180,7 @@ impl EnvironmentTree {
         Rc::new(RefCell::new(EnvironmentNode {
             environment: HashMap::new(),
             parent,
+            charge: Charge::default(),
         }))
     }
 
@@ -133,23 +189,23 @@ impl EnvironmentTree {
         token: &Token,
         value: Literal,
     ) -> Result<(), RuntimeErrorOrReturn> {
-        let env = &mut self.current.borrow_mut().environment;
-        if env.contains_key(&token.lexeme) {
-            env.insert(token.lexeme.clone(), value);
+        if self
+            .current
+            .borrow()
+            .environment
+            .contains_key(&token.lexeme)
+        {
+            EnvironmentNode::insert(&self.current, token.lexeme.clone(), value);
             return Ok(());
         }
-        if let Some(par) = &mut (*self.current).borrow_mut().parent {
-            let env = &mut par.borrow_mut().environment;
-            if env.contains_key(&token.lexeme) {
-                env.insert(token.lexeme.clone(), value);
+        let parent = self.current.borrow().parent.clone();
+        if let Some(parent) = parent {
+            if parent.borrow().environment.contains_key(&token.lexeme) {
+                EnvironmentNode::insert(&parent, token.lexeme.clone(), value);
                 return Ok(());
             }
         }
 
-        return Err(RuntimeErrorOrReturn {
-            message: format!("Undefined variable '{}'.", token.lexeme),
-            token: token.clone(),
-            return_flag: false,
-        });
+        Err(self.undefined_variable(token))
     }
 }
@@ -0,0 +1,702 @@
+use std::fmt;
+
+/// A stable identifier for each kind of diagnostic.
+///
+/// Codes are grouped by phase: `E00xx` scanning, `E01xx` parsing, `E02xx`
+/// resolving, `E03xx` runtime errors and `E04xx` limits set by the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    UnexpectedCharacter,
+    UnterminatedString,
+    UnterminatedComment,
+    InvalidEscape,
+    MalformedNumber,
+    ExpectExpression,
+    ExpectToken,
+    ExpectName,
+    InvalidAssignmentTarget,
+    TooManyParameters,
+    TooManyArguments,
+    AlreadyDeclared,
+    ReadInOwnInitializer,
+    ThisOutsideClass,
+    SuperOutsideClass,
+    SuperWithoutSuperclass,
+    ReturnFromTopLevel,
+    ReturnValueFromInitializer,
+    InheritFromSelf,
+    OperandNotNumber,
+    OperandsNotNumbers,
+    OperandsNotNumbersOrStrings,
+    UndefinedVariable,
+    UndefinedProperty,
+    PropertyOnNonInstance,
+    FieldOnNonInstance,
+    NotCallable,
+    WrongArgumentCount,
+    SuperclassNotClass,
+    StackOverflow,
+    NativeFailure,
+    OutputFailed,
+    StepLimit,
+    TimeLimit,
+    Cancelled,
+    OutOfMemory,
+}
+
+use ErrorCode::*;
+
+impl ErrorCode {
+    pub const ALL: &'static [ErrorCode] = &[
+        UnexpectedCharacter,
+        UnterminatedString,
+        UnterminatedComment,
+        InvalidEscape,
+        MalformedNumber,
+        ExpectExpression,
+        ExpectToken,
+        ExpectName,
+        InvalidAssignmentTarget,
+        TooManyParameters,
+        TooManyArguments,
+        AlreadyDeclared,
+        ReadInOwnInitializer,
+        ThisOutsideClass,
+        SuperOutsideClass,
+        SuperWithoutSuperclass,
+        ReturnFromTopLevel,
+        ReturnValueFromInitializer,
+        InheritFromSelf,
+        OperandNotNumber,
+        OperandsNotNumbers,
+        OperandsNotNumbersOrStrings,
+        UndefinedVariable,
+        UndefinedProperty,
+        PropertyOnNonInstance,
+        FieldOnNonInstance,
+        NotCallable,
+        WrongArgumentCount,
+        SuperclassNotClass,
+        StackOverflow,
+        NativeFailure,
+        OutputFailed,
+        StepLimit,
+        TimeLimit,
+        Cancelled,
+        OutOfMemory,
+    ];
+
+    /// The code as printed, such as `E0103`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UnexpectedCharacter => "E0001",
+            UnterminatedString => "E0002",
+            UnterminatedComment => "E0003",
+            InvalidEscape => "E0004",
+            MalformedNumber => "E0005",
+            ExpectExpression => "E0101",
+            ExpectToken => "E0102",
+            ExpectName => "E0103",
+            InvalidAssignmentTarget => "E0104",
+            TooManyParameters => "E0105",
+            TooManyArguments => "E0106",
+            AlreadyDeclared => "E0201",
+            ReadInOwnInitializer => "E0202",
+            ThisOutsideClass => "E0203",
+            SuperOutsideClass => "E0204",
+            SuperWithoutSuperclass => "E0205",
+            ReturnFromTopLevel => "E0206",
+            ReturnValueFromInitializer => "E0207",
+            InheritFromSelf => "E0208",
+            OperandNotNumber => "E0301",
+            OperandsNotNumbers => "E0302",
+            OperandsNotNumbersOrStrings => "E0303",
+            UndefinedVariable => "E0304",
+            UndefinedProperty => "E0305",
+            PropertyOnNonInstance => "E0306",
+            FieldOnNonInstance => "E0307",
+            NotCallable => "E0308",
+            WrongArgumentCount => "E0309",
+            SuperclassNotClass => "E0310",
+            StackOverflow => "E0311",
+            NativeFailure => "E0312",
+            OutputFailed => "E0313",
+            StepLimit => "E0401",
+            TimeLimit => "E0402",
+            Cancelled => "E0403",
+            OutOfMemory => "E0404",
+        }
+    }
+
+    /// Looks up a code such as `E0103`; the `E` is optional.
+    pub fn parse(code: &str) -> Option<Self> {
+        let code = code.trim();
+        let code = code
+            .strip_prefix('E')
+            .or_else(|| code.strip_prefix('e'))
+            .unwrap_or(code);
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|known| &known.as_str()[1..] == code)
+    }
+
+    /// A longer description with an example that fails and one that works, or
+    /// for limits, the host setting behind them.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            UnexpectedCharacter => {
+                "The scanner found a character that can't start any Lox token.
+
+Erroneous example:
+
+    var total = 3 # 4;
+
+Lox only has the operators `+ - * / ! = < > ( ) { } , . ;` and comments
+start with `//` or `/*`:
+
+    var total = 3 + 4; // seven
+"
+            }
+            UnterminatedString => {
+                "A string literal has no closing quote before the end of the source.
+
+Erroneous example:
+
+    print \"hello;
+
+Close the string:
+
+    print \"hello\";
+"
+            }
+            UnterminatedComment => {
+                "A `/*` block comment has no matching `*/` before the end of the source.
+Block comments nest, so every `/*` inside one needs its own `*/`.
+
+Erroneous example:
+
+    /* disabled for now:
+    /* print \"old\"; */
+    print \"new\";
+
+Close every comment:
+
+    /* disabled for now:
+    /* print \"old\"; */
+    */
+    print \"new\";
+"
+            }
+            InvalidEscape => {
+                "A string contains a backslash that doesn't start a known escape
+sequence. Lox understands `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"`, `\\$` and
+`\\u{XXXX}`, where `XXXX` is one to six hex digits naming a Unicode character.
+
+Erroneous example:
+
+    print \"C:\\temp\";
+
+Escape the backslash itself:
+
+    print \"C:\\\\temp\";
+"
+            }
+            MalformedNumber => {
+                "A number literal is missing digits, has a digit its base doesn't
+allow, or has a `_` that isn't between two digits. Numbers can be written as
+`255`, `2.5`, `6.02e23`, `1_000_000`, or with a prefix as `0xFF` (hex),
+`0b1010` (binary) or `0o17` (octal).
+
+Erroneous example:
+
+    var mask = 0b1012;
+
+Use only the digits of the literal's base:
+
+    var mask = 0b1010;
+"
+            }
+            ExpectExpression => {
+                "The parser needed an expression, such as a value, variable or call,
+but found something else.
+
+Erroneous example:
+
+    var x = 1 + ;
+
+Complete the expression:
+
+    var x = 1 + 2;
+"
+            }
+            ExpectToken => {
+                "A required piece of punctuation or a keyword is missing, such as
+the `;` at the end of a statement or the `)` closing a condition.
+
+Erroneous example:
+
+    if (ready print \"go\";
+
+Add what the message asks for:
+
+    if (ready) print \"go\";
+"
+            }
+            ExpectName => {
+                "A declaration, parameter list or property access needs an identifier.
+
+Erroneous example:
+
+    var 1st = \"first\";
+
+Names start with a letter or `_`:
+
+    var first = \"first\";
+"
+            }
+            InvalidAssignmentTarget => {
+                "Only variables and fields can be assigned to.
+
+Erroneous example:
+
+    var a = 1;
+    a + 1 = 3;
+
+Assign to a variable or a field:
+
+    var a = 1;
+    a = 3;
+"
+            }
+            TooManyParameters => {
+                "A function can't declare more than 255 parameters.
+
+Erroneous example:
+
+    fun f(p1, p2, /* ... */ p256) {}
+
+Group related values into an instance and pass that instead:
+
+    fun f(options) {}
+"
+            }
+            TooManyArguments => {
+                "A call can't pass more than 255 arguments.
+
+Erroneous example:
+
+    f(a1, a2, /* ... */ a256);
+
+Group related values into an instance and pass that instead:
+
+    f(options);
+"
+            }
+            AlreadyDeclared => {
+                "A local scope declares the same name twice. Global variables may be
+redeclared, but locals may not.
+
+Erroneous example:
+
+    {
+      var a = 1;
+      var a = 2;
+    }
+
+Assign to the existing variable instead:
+
+    {
+      var a = 1;
+      a = 2;
+    }
+"
+            }
+            ReadInOwnInitializer => {
+                "A local variable's initializer refers to the variable being declared.
+
+Erroneous example:
+
+    var a = \"outer\";
+    {
+      var a = a;
+    }
+
+Use a different name for the new variable:
+
+    var a = \"outer\";
+    {
+      var b = a;
+    }
+"
+            }
+            ThisOutsideClass => {
+                "`this` only means something inside a method.
+
+Erroneous example:
+
+    fun show() {
+      print this;
+    }
+
+Make the function a method:
+
+    class Thing {
+      show() {
+        print this;
+      }
+    }
+"
+            }
+            SuperOutsideClass => {
+                "`super` only means something inside a method of a subclass.
+
+Erroneous example:
+
+    fun greet() {
+      super.greet();
+    }
+
+Call it from a method of a class with a superclass:
+
+    class Child < Parent {
+      greet() {
+        super.greet();
+      }
+    }
+"
+            }
+            SuperWithoutSuperclass => {
+                "`super` is used in a class that doesn't inherit from anything.
+
+Erroneous example:
+
+    class Child {
+      greet() {
+        super.greet();
+      }
+    }
+
+Declare the superclass:
+
+    class Child < Parent {
+      greet() {
+        super.greet();
+      }
+    }
+"
+            }
+            ReturnFromTopLevel => {
+                "`return` can only appear inside a function or method.
+
+Erroneous example:
+
+    return 1;
+
+Put the code in a function:
+
+    fun one() {
+      return 1;
+    }
+"
+            }
+            ReturnValueFromInitializer => {
+                "An `init` method always returns the new instance, so it can't return
+another value. A bare `return;` is allowed.
+
+Erroneous example:
+
+    class Point {
+      init(x) {
+        this.x = x;
+        return 0;
+      }
+    }
+
+Leave the return value out:
+
+    class Point {
+      init(x) {
+        this.x = x;
+        return;
+      }
+    }
+"
+            }
+            InheritFromSelf => {
+                "A class names itself as its own superclass.
+
+Erroneous example:
+
+    class Node < Node {}
+
+Inherit from a different class:
+
+    class Base {}
+    class Node < Base {}
+"
+            }
+            OperandNotNumber => {
+                "Unary `-` only works on numbers.
+
+Erroneous example:
+
+    print -\"five\";
+
+Negate a number:
+
+    print -5;
+"
+            }
+            OperandsNotNumbers => {
+                "Arithmetic and comparison operators other than `+` only work on numbers.
+
+Erroneous example:
+
+    print \"10\" > 9;
+
+Compare numbers:
+
+    print 10 > 9;
+"
+            }
+            OperandsNotNumbersOrStrings => {
+                "`+` adds two numbers or joins two strings, but can't mix them.
+
+Erroneous example:
+
+    print \"total: \" + 3;
+
+Use two strings or two numbers:
+
+    print \"total: \" + \"3\";
+"
+            }
+            UndefinedVariable => {
+                "A variable is read or assigned before it is declared.
+
+Erroneous example:
+
+    count = 1;
+
+Declare it with `var` first:
+
+    var count = 1;
+"
+            }
+            UndefinedProperty => {
+                "An instance has neither a field nor a method with this name.
+
+Erroneous example:
+
+    class Point {}
+    print Point().x;
+
+Set the field before reading it:
+
+    class Point {}
+    var p = Point();
+    p.x = 1;
+    print p.x;
+"
+            }
+            PropertyOnNonInstance => {
+                "Only instances have properties; numbers, strings, functions and
+classes don't.
+
+Erroneous example:
+
+    var name = \"lox\";
+    print name.length;
+
+Read properties from an instance:
+
+    class Word {}
+    var name = Word();
+    name.length = 3;
+    print name.length;
+"
+            }
+            FieldOnNonInstance => {
+                "Only instances have fields that can be set.
+
+Erroneous example:
+
+    var point = 3;
+    point.x = 1;
+
+Create an instance first:
+
+    class Point {}
+    var point = Point();
+    point.x = 1;
+"
+            }
+            NotCallable => {
+                "Only functions and classes can be called.
+
+Erroneous example:
+
+    var greeting = \"hi\";
+    greeting();
+
+Call a function:
+
+    fun greeting() { print \"hi\"; }
+    greeting();
+"
+            }
+            WrongArgumentCount => {
+                "A call passes a different number of arguments than the function or
+the class's `init` method declares.
+
+Erroneous example:
+
+    fun add(a, b) { return a + b; }
+    print add(1);
+
+Pass every argument:
+
+    fun add(a, b) { return a + b; }
+    print add(1, 2);
+"
+            }
+            SuperclassNotClass => {
+                "The name after `<` in a class declaration isn't a class.
+
+Erroneous example:
+
+    var Base = \"base\";
+    class Derived < Base {}
+
+Inherit from a class:
+
+    class Base {}
+    class Derived < Base {}
+"
+            }
+            StackOverflow => {
+                "Calls nested deeper than the engine allows, usually because a
+recursive function has no base case.
+
+Erroneous example:
+
+    fun count(n) { return count(n + 1); }
+    count(0);
+
+Stop the recursion:
+
+    fun count(n) {
+      if (n == 10) return n;
+      return count(n + 1);
+    }
+    count(0);
+"
+            }
+            NativeFailure => {
+                "A function implemented by the host, such as `readFile`, failed. The
+message says why.
+
+Erroneous example:
+
+    print readFile(\"missing.txt\");
+
+Pass arguments the function can work with:
+
+    print readFile(\"present.txt\");
+"
+            }
+            OutputFailed => {
+                "A `print` statement couldn't write to the output the host gave the
+engine, for example because the disk is full or the program reading the output
+has exited.
+
+Erroneous example, run as `rlox hello.lox > /dev/full`:
+
+    print \"hello\";
+
+Send the output somewhere that can take it, as with `rlox hello.lox > hello.txt`:
+
+    print \"hello\";
+"
+            }
+            StepLimit => {
+                "The run executed more statements than the host allows. The host sets
+the budget with `Limits::max_steps`, and every call into the engine gets it
+afresh. The `rlox` command line sets no step limit.
+
+A script that never stops, such as `while (true) {}`, always ends this way
+when there is a budget. Otherwise the host can raise `max_steps`, or leave it
+`None` for no limit.
+"
+            }
+            TimeLimit => {
+                "The run took longer than the host allows. The host sets the budget
+with `Limits::timeout`, and every call into the engine gets it afresh. It is
+wall-clock time, so time spent in native functions counts too.
+
+The host can raise `timeout`, or leave it `None` for no limit.
+"
+            }
+            Cancelled => {
+                "The host stopped the run by setting the flag it passed as
+`Limits::cancel`, usually because a user asked it to stop or the result was no
+longer needed. The run stops at the next statement it executes.
+
+The engine never clears the flag. The host has to set it back to `false`
+before it runs anything else, or every later run stops at its first statement.
+"
+            }
+            OutOfMemory => {
+                "The strings, instances, environments and variables the engine holds
+took up more memory than the host allows with `Limits::max_memory`. Unlike the
+other limits, it counts everything still alive, including globals defined by
+earlier runs.
+
+The host can raise `max_memory`, or leave it `None` for no limit. A script can
+stay under it by not keeping what it no longer needs, for example in a global
+list.
+"
+            }
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_limit(code: &ErrorCode) -> bool {
+        code.as_str().starts_with("E04")
+    }
+
+    #[test]
+    fn every_explanation_has_a_bad_and_a_fixed_example() {
+        for code in ErrorCode::ALL.iter().filter(|code| !is_limit(code)) {
+            let explanation = code.explanation();
+            let examples = explanation.matches("\n\n    ").count();
+            assert!(explanation.contains("Erroneous example"), "{}", code);
+            assert!(examples >= 2, "{} has {} examples", code, examples);
+        }
+    }
+
+    #[test]
+    fn limit_explanations_name_their_setting() {
+        for code in ErrorCode::ALL.iter().filter(|code| is_limit(code)) {
+            assert!(code.explanation().contains("`Limits::"), "{}", code);
+        }
+    }
+
+    #[test]
+    fn codes_parse_back() {
+        for &code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::parse(code.as_str()), Some(code));
+        }
+    }
+}
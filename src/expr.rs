@@ -16,6 +16,7 @@ pub(crate) enum Expr {
     SetExpr(Box<Set>),
     ThisExpr(Box<This>),
     SuperExpr(Box<Super>),
+    InterpolationExpr(Box<Interpolation>),
 }
 
 impl Expr {
@@ -33,9 +34,31 @@ impl Expr {
             Expr::SetExpr(expr) => visitor.visit_set_expr(expr),
             Expr::ThisExpr(expr) => visitor.visit_this_expr(expr),
             Expr::SuperExpr(expr) => visitor.visit_super_expr(expr),
+            Expr::InterpolationExpr(expr) => visitor.visit_interpolation_expr(expr),
         }
     }
 }
+impl Expr {
+    /// The token that best locates the expression, if it has one.
+    pub(crate) fn token(&self) -> Option<&Token> {
+        match self {
+            Expr::BinaryExpr(expr) => Some(&expr.operator),
+            Expr::GroupingExpr(expr) => expr.expression.token(),
+            Expr::LiteralExprExpr(_) => None,
+            Expr::UnaryExpr(expr) => Some(&expr.operator),
+            Expr::VariableExpr(expr) => Some(&expr.name),
+            Expr::AssignExpr(expr) => Some(&expr.name),
+            Expr::LogicalExpr(expr) => Some(&expr.operator),
+            Expr::CallExpr(expr) => Some(&expr.paren),
+            Expr::GetExpr(expr) => Some(&expr.name),
+            Expr::SetExpr(expr) => Some(&expr.name),
+            Expr::ThisExpr(expr) => Some(&expr.keyword),
+            Expr::SuperExpr(expr) => Some(&expr.keyword),
+            Expr::InterpolationExpr(expr) => expr.parts.iter().find_map(Expr::token),
+        }
+    }
+}
+
 pub(crate) trait Visitor<R> {
     fn visit_binary_expr(&mut self, expr: &Binary) -> R;
     fn visit_grouping_expr(&mut self, expr: &Grouping) -> R;
@@ -49,6 +72,7 @@ pub(crate) trait Visitor<R> {
     fn visit_set_expr(&mut self, expr: &Set) -> R;
     fn visit_this_expr(&mut self, expr: &This) -> R;
     fn visit_super_expr(&mut self, expr: &Super) -> R;
+    fn visit_interpolation_expr(&mut self, expr: &Interpolation) -> R;
 }
 
 pub(crate) struct Binary {
@@ -206,3 +230,15 @@ impl Super {
         })
     }
 }
+
+/// A string literal with `${...}` in it: the literal pieces and the embedded
+/// expressions, in source order.
+pub(crate) struct Interpolation {
+    pub(crate) parts: Vec<Expr>,
+}
+
+impl Interpolation {
+    pub(crate) fn new(parts: Vec<Expr>) -> Box<Self> {
+        Box::new(Self { parts })
+    }
+}
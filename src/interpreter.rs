@@ -1,17 +1,26 @@
+use crate::diagnostic::{Diagnostic, Diagnostics, Frame, Location, Phase};
 use crate::environment_tree::{EnvironmentNode, EnvironmentTree};
+use crate::error_code::ErrorCode;
 use crate::expr::{
-    self, Assign, Binary, Call, Expr, Expr::VariableExpr, Grouping, LiteralExpr, Logical, Unary,
-    Variable,
+    self, Assign, Binary, Call, Expr, Expr::VariableExpr, Grouping, Interpolation, LiteralExpr,
+    Logical, Unary, Variable,
 };
-use crate::lox_callable::{LoxCallable, LoxClass, LoxFunction};
+use crate::limits::{binding_size, Allocated, Charge, Interrupt, Limits};
+use crate::lox_callable::{Closure, LoxCallable, LoxClass, LoxFunction, Method};
+use crate::span::Span;
+use crate::stmt;
 use crate::stmt::{Block, Expression, Function, If, Print, Return, Stmt, Var, While};
+use crate::suggest;
 use crate::token::{Literal, Token};
 use crate::token_type::TokenType::*;
-use crate::{runtime_error, stmt};
 use std::borrow::Borrow;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::io::Write;
+use std::mem;
 use std::rc::Rc;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
 
 pub(crate) type ExprVisitorResult = Result<Literal, RuntimeErrorOrReturn>;
 type StmtVisitorResult = Result<(), RuntimeErrorOrReturn>;
@@ -19,25 +28,247 @@ type StmtVisitorResult = Result<(), RuntimeErrorOrReturn>;
 pub(crate) struct Interpreter {
     pub(crate) env: Rc<RefCell<EnvironmentTree>>,
     pub(crate) return_value: Option<Literal>,
+    output: Box<dyn Write>,
+    pub(crate) limits: Limits,
+    pub(crate) interrupt: Option<Interrupt>,
+    steps: usize,
+    /// What each call in progress called, and from where.
+    call_stack: Vec<(LoxCallable, Span)>,
+    /// The call stack when the current error left its innermost call.
+    trace: Option<Vec<Frame>>,
+    started: Instant,
+    /// Where the native stack was when the run started.
+    stack_base: usize,
+    line: usize,
+    pub(crate) allocated: Allocated,
 }
 impl Interpreter {
+    pub(crate) fn new(env: Rc<RefCell<EnvironmentTree>>, output: Box<dyn Write>) -> Self {
+        Self {
+            env,
+            return_value: None,
+            output,
+            limits: Limits::default(),
+            interrupt: None,
+            steps: 0,
+            call_stack: Vec::new(),
+            trace: None,
+            started: Instant::now(),
+            stack_base: stack_position(),
+            line: 0,
+            allocated: Allocated::default(),
+        }
+    }
+
+    /// Gives the next run a fresh budget.
+    pub(crate) fn start_run(&mut self) {
+        self.interrupt = None;
+        self.trace = None;
+        self.steps = 0;
+        self.started = Instant::now();
+        self.stack_base = stack_position();
+        self.line = 0;
+    }
+
     fn evaluate(&mut self, expr: &Expr) -> ExprVisitorResult {
         expr.accept(self)
     }
 
     pub(crate) fn execute(&mut self, stmt: &Stmt) -> StmtVisitorResult {
+        if let Some(token) = stmt.token() {
+            self.line = token.span.line as usize;
+        }
+        if let Some(interrupt) = self.check_limits() {
+            return Err(self.interrupted(interrupt));
+        }
         stmt.accept(self)
     }
 
-    pub(crate) fn interpret(&mut self, statements: Vec<Stmt>) -> Result<(), RuntimeErrorOrReturn> {
+    fn interrupted(&mut self, interrupt: Interrupt) -> RuntimeErrorOrReturn {
+        self.interrupt = Some(interrupt);
+        RuntimeErrorOrReturn {
+            message: interrupt.message().into(),
+            code: Some(interrupt.code()),
+            token: Token::from(
+                Eof,
+                String::new(),
+                Literal::NoneLiteral,
+                Span::at_line(self.line),
+            ),
+            return_flag: false,
+            help: None,
+        }
+    }
+
+    /// Counts `bytes` against the memory limit, if there is one.
+    pub(crate) fn allocate(&mut self, bytes: usize) -> StmtVisitorResult {
+        if let Some(max_memory) = self.limits.max_memory {
+            self.allocated.set(self.allocated.get() + bytes);
+            if self.allocated.get() > max_memory {
+                return Err(self.interrupted(Interrupt::OutOfMemory));
+            }
+        }
+        Ok(())
+    }
+
+    fn release(&mut self, bytes: usize) {
+        self.allocated
+            .set(self.allocated.get().saturating_sub(bytes));
+    }
+
+    /// Counts `charged` more and `released` fewer bytes against the memory
+    /// limit, on behalf of the environment or instance that owns `charge`.
+    pub(crate) fn account(
+        &mut self,
+        charge: &mut Charge,
+        released: usize,
+        charged: usize,
+    ) -> StmtVisitorResult {
+        if self.limits.max_memory.is_none() {
+            return Ok(());
+        }
+        charge.update(&self.allocated, released, charged);
+        self.release(released);
+        self.allocate(charged)
+    }
+
+    fn define(&mut self, name: String, value: Literal) -> StmtVisitorResult {
+        let size = binding_size(&name, &value);
+        let name_len = name.len();
+        let previous = (*self.env).borrow().define_current(name, value);
+        let released = previous.map_or(0, |previous| name_len + binding_size("", &previous));
+        let node = (*self.env).borrow().get_current();
+        let result = self.account(&mut node.borrow_mut().charge, released, size);
+        result
+    }
+
+    fn check_limits(&mut self) -> Option<Interrupt> {
+        self.steps += 1;
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                return Some(Interrupt::StepLimit);
+            }
+        }
+        if let Some(timeout) = self.limits.timeout {
+            if self.started.elapsed() > timeout {
+                return Some(Interrupt::Timeout);
+            }
+        }
+        if let Some(cancel) = &self.limits.cancel {
+            if cancel.load(Ordering::Relaxed) {
+                return Some(Interrupt::Cancelled);
+            }
+        }
+        None
+    }
+
+    /// Interrupts aren't caused by a particular token, so they only report the line.
+    pub(crate) fn diagnose(&mut self, error: &RuntimeErrorOrReturn) -> Diagnostic {
+        let mut diagnostic = if self.interrupt.is_some() {
+            let location = if error.token.span.line == 0 {
+                Location::Host
+            } else {
+                Location::Line
+            };
+            Diagnostic::error(
+                Phase::Runtime,
+                error.code.expect("interrupts have a code"),
+                error.token.span.line as usize,
+                location,
+                &error.message,
+            )
+        } else {
+            Diagnostic::from_runtime_error(error)
+        };
+        diagnostic.trace = self.trace.take().unwrap_or_default();
+        diagnostic
+    }
+
+    fn backtrace(&self) -> Vec<Frame> {
+        self.call_stack
+            .iter()
+            .rev()
+            .map(|(callee, call_site)| Frame {
+                function: callee.name(),
+                line: call_site.line as usize,
+                call_site: (call_site.line > 0).then_some(*call_site),
+            })
+            .collect()
+    }
+
+    pub(crate) fn interpret(
+        &mut self,
+        statements: Vec<Stmt>,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<(), RuntimeErrorOrReturn> {
         for statement in &statements {
             if let Err(err) = self.execute(statement) {
-                runtime_error(&err);
+                diagnostics.push(self.diagnose(&err));
                 return Err(err);
             }
         }
         Ok(())
     }
+
+    pub(crate) fn call_value(
+        &mut self,
+        callee: Literal,
+        arguments: Vec<Literal>,
+        paren: &Token,
+    ) -> ExprVisitorResult {
+        if let Literal::Callable(calleable) = callee {
+            let arity = calleable.arity();
+            if !arity.accepts(arguments.len()) {
+                return Err(RuntimeErrorOrReturn {
+                    message: arity.mismatch_message(arguments.len()).into(),
+                    code: Some(ErrorCode::WrongArgumentCount),
+                    token: paren.clone(),
+                    return_flag: false,
+                    help: None,
+                });
+            }
+            if self.call_stack.len() >= self.limits.max_call_depth
+                || stack_position().abs_diff(self.stack_base) > self.limits.max_stack
+            {
+                return Err(RuntimeErrorOrReturn {
+                    message: "Stack overflow.".into(),
+                    code: Some(ErrorCode::StackOverflow),
+                    token: paren.clone(),
+                    return_flag: false,
+                    help: None,
+                });
+            }
+            self.call_stack.push((calleable.clone(), paren.span));
+            let result = calleable.call(self, arguments, paren);
+            if result.is_err() && self.trace.is_none() {
+                self.trace = Some(self.backtrace());
+            }
+            self.call_stack.pop();
+            return result;
+        }
+        Err(RuntimeErrorOrReturn {
+            message: "Can only call functions and classes.".into(),
+            code: Some(ErrorCode::NotCallable),
+            token: paren.clone(),
+            return_flag: false,
+            help: None,
+        })
+    }
+
+    pub(crate) fn get_property(&self, object: Literal, name: &Token) -> ExprVisitorResult {
+        if let Literal::Instance(inst) = object {
+            (*inst).borrow().get(inst.clone(), name)
+        } else {
+            Err(RuntimeErrorOrReturn {
+                message: "Only instances have properties.".into(),
+                code: Some(ErrorCode::PropertyOnNonInstance),
+                token: name.clone(),
+                return_flag: false,
+                help: None,
+            })
+        }
+    }
+
     fn stringify(&self, literal: &Literal) -> String {
         literal.stringify()
     }
@@ -47,39 +278,59 @@ impl Interpreter {
         statements: &[Stmt],
         environment: Rc<RefCell<EnvironmentNode>>,
     ) -> StmtVisitorResult {
+        let size = mem::size_of::<EnvironmentNode>();
+        self.account(&mut environment.borrow_mut().charge, 0, size)?;
         let previous = (*self.env).borrow().get_current();
-        (*self.env).borrow_mut().set_current(environment);
-        for statement in statements {
-            if let Err(err) = self.execute(statement) {
-                (*self.env).borrow_mut().set_current(previous);
-                return Err(err);
-            }
-        }
+        (*self.env).borrow_mut().set_current(environment.clone());
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement));
         (*self.env).borrow_mut().set_current(previous);
-        Ok(())
+        result
     }
 }
 
 pub(crate) struct RuntimeErrorOrReturn {
-    pub(crate) message: String,
+    pub(crate) message: Box<str>,
+    /// `None` for a `return`, which unwinds the same way an error does.
+    pub(crate) code: Option<ErrorCode>,
     pub(crate) token: Token,
     pub(crate) return_flag: bool,
+    /// A suggestion for fixing the error, such as a similar name.
+    pub(crate) help: Option<Box<str>>,
 }
 
-fn construct_error(message: &str, token: &Token) -> ExprVisitorResult {
+/// The address of a local variable, which tells how deep the native stack is.
+#[inline(never)]
+fn stack_position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+fn construct_error(code: ErrorCode, message: &str, token: &Token) -> ExprVisitorResult {
     Err(RuntimeErrorOrReturn {
-        message: String::from(message),
+        message: message.into(),
+        code: Some(code),
         token: token.clone(),
         return_flag: false,
+        help: None,
     })
 }
 
 fn construct_number_error(token: &Token) -> ExprVisitorResult {
-    construct_error("Operand must be a number.", token)
+    construct_error(
+        ErrorCode::OperandNotNumber,
+        "Operand must be a number.",
+        token,
+    )
 }
 
 fn construct_numbers_error(token: &Token) -> ExprVisitorResult {
-    construct_error("Operands must be numbers.", token)
+    construct_error(
+        ErrorCode::OperandsNotNumbers,
+        "Operands must be numbers.",
+        token,
+    )
 }
 
 impl expr::Visitor<ExprVisitorResult> for Interpreter {
@@ -116,11 +367,18 @@ impl expr::Visitor<ExprVisitorResult> for Interpreter {
         }
         if expr.operator.token_type == Plus {
             if left.is_string() && right.is_string() {
+                let length = left.unwrap_str_literal().len() + right.unwrap_str_literal().len();
+                self.allocate(length)?;
+                self.release(length);
                 let mut concat_string = String::from(left.unwrap_str_literal());
                 concat_string.push_str(right.unwrap_str_literal());
                 return Ok(Literal::wrap_string_literal(concat_string));
             } else if !right.is_float() || !left.is_float() {
-                return construct_error("Operands must be two numbers or two strings.", &expr.operator)
+                return construct_error(
+                    ErrorCode::OperandsNotNumbersOrStrings,
+                    "Operands must be two numbers or two strings.",
+                    &expr.operator,
+                );
             }
         }
 
@@ -143,15 +401,30 @@ impl expr::Visitor<ExprVisitorResult> for Interpreter {
         }
     }
 
+    fn visit_interpolation_expr(&mut self, expr: &Interpolation) -> ExprVisitorResult {
+        let mut string = String::new();
+        for part in &expr.parts {
+            string.push_str(&self.evaluate(part)?.stringify());
+        }
+        self.allocate(string.len())?;
+        self.release(string.len());
+        Ok(Literal::wrap_string_literal(string))
+    }
+
     fn visit_variable_expr(&mut self, expr: &Variable) -> ExprVisitorResult {
         (*self.env).borrow().get(&expr.name, expr.id)
     }
 
     fn visit_assign_expr(&mut self, expr: &Assign) -> ExprVisitorResult {
         let value = self.evaluate(&expr.value)?;
-        self.env
+        let previous = self
+            .env
             .borrow_mut()
             .assign(&expr.name, value.clone(), expr.id)?;
+        let released = binding_size(&expr.name.lexeme, &previous);
+        let size = binding_size(&expr.name.lexeme, &value);
+        let node = (*self.env).borrow().node_for(expr.id);
+        self.account(&mut node.borrow_mut().charge, released, size)?;
         Ok(value)
     }
 
@@ -161,12 +434,10 @@ impl expr::Visitor<ExprVisitorResult> for Interpreter {
             if left.is_truthy() {
                 return Ok(left);
             }
-        } else {
-            if !left.is_truthy() {
-                return Ok(left);
-            }
+        } else if !left.is_truthy() {
+            return Ok(left);
         }
-        return self.evaluate(&expr.right);
+        self.evaluate(&expr.right)
     }
 
     fn visit_call_expr(&mut self, expr: &Call) -> ExprVisitorResult {
@@ -175,51 +446,31 @@ impl expr::Visitor<ExprVisitorResult> for Interpreter {
         for argument in &expr.arguments {
             arguments.push(self.evaluate(argument)?);
         }
-        if let Literal::Callable(calleable) = callee {
-            if arguments.len() != calleable.arity() {
-                return Err(RuntimeErrorOrReturn {
-                    message: format!(
-                        "Expected {} arguments but got {}.",
-                        calleable.arity(),
-                        arguments.len()
-                    ),
-                    token: expr.paren.clone(),
-                    return_flag: false,
-                });
-            }
-            return calleable.call(self, arguments);
-        }
-        Err(RuntimeErrorOrReturn {
-            message: "Can only call functions and classes.".to_string(),
-            token: expr.paren.clone(),
-            return_flag: false,
-        })
+        self.call_value(callee, arguments, &expr.paren)
     }
 
     fn visit_get_expr(&mut self, expr: &expr::Get) -> ExprVisitorResult {
         let object = self.evaluate(&expr.object)?;
-        if let Literal::Instance(inst) = object {
-            Ok((*inst).borrow().get(inst.clone(), &expr.name)?)
-        } else {
-            Err(RuntimeErrorOrReturn {
-                message: "Only instances have properties.".to_string(),
-                token: expr.name.clone(),
-                return_flag: false,
-            })
-        }
+        self.get_property(object, &expr.name)
     }
 
     fn visit_set_expr(&mut self, expr: &expr::Set) -> ExprVisitorResult {
         let object = self.evaluate(&expr.object)?;
         if let Literal::Instance(inst) = object {
             let value = self.evaluate(&expr.value)?;
-            (*inst).borrow_mut().set(&expr.name, value.clone());
+            let previous = (*inst).borrow_mut().set(&expr.name, value.clone());
+            let released =
+                previous.map_or(0, |previous| binding_size(&expr.name.lexeme, &previous));
+            let size = binding_size(&expr.name.lexeme, &value);
+            self.account(&mut inst.borrow_mut().charge, released, size)?;
             Ok(value)
         } else {
             Err(RuntimeErrorOrReturn {
-                message: "Only instances have fields.".to_string(),
+                message: "Only instances have fields.".into(),
+                code: Some(ErrorCode::FieldOnNonInstance),
                 token: expr.name.clone(),
                 return_flag: false,
+                help: None,
             })
         }
     }
@@ -236,16 +487,21 @@ impl expr::Visitor<ExprVisitorResult> for Interpreter {
             let method_option = (*class).borrow().find_method(&expr.method.lexeme);
             if let Some(method) = method_option {
                 if let Literal::Instance(inst) = object {
-                    return Ok(method.bind(inst));
+                    Ok(method.bind(inst))
                 } else {
                     panic!() //Should not reach here.
                 }
             } else {
-                return Err(RuntimeErrorOrReturn {
-                    message: format!("Undefined property '{}'.", expr.method.lexeme).to_string(),
+                Err(RuntimeErrorOrReturn {
+                    message: format!("Undefined property '{}'.", expr.method.lexeme).into(),
+                    code: Some(ErrorCode::UndefinedProperty),
                     token: expr.method.clone(),
                     return_flag: false,
-                });
+                    help: suggest::did_you_mean(
+                        &expr.method.lexeme,
+                        (*class).borrow().method_names().iter().map(String::as_str),
+                    ),
+                })
             }
         } else {
             panic!() //Should not reach here.
@@ -261,16 +517,22 @@ impl stmt::Visitor<StmtVisitorResult> for Interpreter {
 
     fn visit_print_stmt(&mut self, stmt: &Print) -> StmtVisitorResult {
         let value = self.evaluate(stmt.expression.borrow())?;
-        println!("{}", self.stringify(&value));
+        let text = self.stringify(&value);
+        if let Err(error) = writeln!(self.output, "{}", text) {
+            return Err(RuntimeErrorOrReturn {
+                message: format!("Can't write output: {}.", error).into(),
+                code: Some(ErrorCode::OutputFailed),
+                token: stmt.keyword.clone(),
+                return_flag: false,
+                help: None,
+            });
+        }
         Ok(())
     }
 
     fn visit_var_stmt(&mut self, stmt: &Var) -> StmtVisitorResult {
         let literal = self.evaluate(stmt.initializer.borrow())?;
-        (*self.env)
-            .borrow()
-            .define_current(stmt.name.lexeme.clone(), literal);
-        Ok(())
+        self.define(stmt.name.lexeme.clone(), literal)
     }
 
     fn visit_block_stmt(&mut self, stmt: &Block) -> StmtVisitorResult {
@@ -300,23 +562,30 @@ impl stmt::Visitor<StmtVisitorResult> for Interpreter {
     }
 
     fn visit_function_stmt(&mut self, stmt: Rc<Function>) -> StmtVisitorResult {
-        (*self.env).borrow().define_current(
+        let function = LoxFunction {
+            closure: Closure::Strong((*self.env).borrow().get_current()),
+            declaration: stmt.clone(),
+            is_initializer: false,
+        };
+        self.define(
             stmt.name.lexeme.clone(),
-            Literal::Callable(LoxCallable::UserFunction(LoxFunction {
-                declaration: stmt,
-                closure: (*self.env).borrow().get_current(),
-                is_initializer: false,
-            })),
-        );
+            Literal::Callable(LoxCallable::UserFunction(function)),
+        )
+    }
+
+    fn visit_error_stmt(&mut self, _stmt: &stmt::Error) -> StmtVisitorResult {
+        // Programs that failed to parse never run.
         Ok(())
     }
 
     fn visit_return_stmt(&mut self, stmt: &Return) -> StmtVisitorResult {
         self.return_value = Some(self.evaluate(&stmt.value)?);
         Err(RuntimeErrorOrReturn {
-            message: String::new(),
+            message: Box::default(),
+            code: None,
             token: stmt.keyword.clone(),
             return_flag: true,
+            help: None,
         })
     }
 
@@ -330,16 +599,16 @@ impl stmt::Visitor<StmtVisitorResult> for Interpreter {
                 superclass_literal = literal;
             } else {
                 return Err(RuntimeErrorOrReturn {
-                    message: "Superclass must be a class.".to_string(),
+                    message: "Superclass must be a class.".into(),
+                    code: Some(ErrorCode::SuperclassNotClass),
                     token: variable.name.clone(),
                     return_flag: false,
+                    help: None,
                 });
             }
         }
-        (*self.env)
-            .borrow()
-            .define_current((*stmt).name.lexeme.clone(), Literal::NoneLiteral);
-        if let Some(_) = superclass {
+        self.define(stmt.name.lexeme.clone(), Literal::NoneLiteral)?;
+        if superclass.is_some() {
             let new_env =
                 EnvironmentTree::new_environment_node(Some((*self.env).borrow().get_current()));
             new_env
@@ -350,15 +619,15 @@ impl stmt::Visitor<StmtVisitorResult> for Interpreter {
         }
         let mut methods = HashMap::new();
         for method in &stmt.methods {
-            let function = Literal::Callable(LoxCallable::UserFunction(LoxFunction {
+            let function = Method::User(LoxFunction {
                 declaration: method.clone(),
-                closure: (*self.env).borrow().get_current(),
+                closure: Closure::Strong((*self.env).borrow().get_current()),
                 is_initializer: method.name.lexeme == "init",
-            }));
+            });
             methods.insert(method.name.lexeme.clone(), function);
         }
-        let class = LoxClass::new(stmt.clone(), superclass.clone(), methods);
-        if let Some(_) = superclass {
+        let class = LoxClass::new(stmt.name.lexeme.clone(), superclass.clone(), methods);
+        if superclass.is_some() {
             let cur_env = (*self.env).borrow().get_current();
             let par_env = (*cur_env).borrow().parent.clone().unwrap();
             (*self.env).borrow_mut().set_current(par_env);
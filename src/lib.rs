@@ -0,0 +1,37 @@
+#![allow(clippy::enum_variant_names)]
+
+mod conversion;
+mod diagnostic;
+mod environment_tree;
+mod error_code;
+mod expr;
+mod interpreter;
+mod limits;
+mod lox;
+mod lox_callable;
+mod lox_instance;
+mod parser;
+mod render;
+mod resolver;
+mod scanner;
+mod span;
+mod stdlib;
+mod stmt;
+mod suggest;
+mod token;
+mod token_type;
+mod trivia;
+
+pub use conversion::{ConversionError, FromLox, IntoLox};
+pub use diagnostic::{Diagnostic, Frame, Label, Location, Phase, Severity};
+pub use error_code::ErrorCode;
+pub use limits::{Interrupt, Limits, DEFAULT_MAX_CALL_DEPTH, DEFAULT_MAX_STACK};
+pub use lox::{Config, Lox, LoxError};
+pub use lox_callable::{Arity, NativeClass, NativeError, NativeResult};
+pub use lox_instance::LoxInstance;
+pub use render::DiagnosticFormat;
+pub use span::{FileId, Span};
+pub use stdlib::Capabilities;
+pub use token::Literal;
+pub use token_type::TokenType;
+pub use trivia::{SyntaxToken, Trivia, TriviaKind};
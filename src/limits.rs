@@ -0,0 +1,238 @@
+use std::{
+    cell::Cell,
+    mem,
+    rc::Rc,
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+
+use crate::{error_code::ErrorCode, token::Literal};
+
+/// How deeply Lox calls may nest by default.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+/// How much native stack a run may use by default. It leaves room to spare on
+/// a 1 MiB thread, the smallest a host is likely to run the engine on; hosts
+/// that give the engine a bigger stack can raise it.
+pub const DEFAULT_MAX_STACK: usize = 512 * 1024;
+
+/// Budgets that stop a run before it finishes on its own.
+///
+/// Each call to [`crate::Lox::eval`] or one of the host call methods gets the
+/// full step, time and stack budget again.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// The maximum number of statements executed.
+    pub max_steps: Option<usize>,
+    /// The maximum wall-clock time.
+    pub timeout: Option<Duration>,
+    /// Stops the run as soon as it is set; the host is responsible for clearing it.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Calls nested deeper than this fail with a "Stack overflow." runtime error.
+    pub max_call_depth: usize,
+    /// How many bytes of native stack a run may use before calls fail with a
+    /// "Stack overflow." runtime error. It must be smaller than the stack of
+    /// the thread the engine runs on, by enough for one Lox call and the host.
+    ///
+    /// How deep that lets calls nest depends on the build: a release build
+    /// needs a few KiB per call, a debug build several times more.
+    pub max_stack: usize,
+    /// The approximate number of bytes that strings, instances, their fields and
+    /// environments may take up.
+    ///
+    /// Unlike the other budgets, it counts everything still alive, including
+    /// globals defined by earlier runs. Environments and instances are given
+    /// back when the last reference to them goes, except that a class declared
+    /// inside a function keeps that call's environment alive.
+    pub max_memory: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_steps: None,
+            timeout: None,
+            cancel: None,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_stack: DEFAULT_MAX_STACK,
+            max_memory: None,
+        }
+    }
+}
+
+/// Why a run was stopped by the host rather than by an error in the script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    StepLimit,
+    Timeout,
+    Cancelled,
+    OutOfMemory,
+}
+
+impl Interrupt {
+    pub(crate) fn code(&self) -> ErrorCode {
+        match self {
+            Interrupt::StepLimit => ErrorCode::StepLimit,
+            Interrupt::Timeout => ErrorCode::TimeLimit,
+            Interrupt::Cancelled => ErrorCode::Cancelled,
+            Interrupt::OutOfMemory => ErrorCode::OutOfMemory,
+        }
+    }
+
+    pub(crate) fn message(&self) -> &'static str {
+        match self {
+            Interrupt::StepLimit => "Step limit exceeded.",
+            Interrupt::Timeout => "Time limit exceeded.",
+            Interrupt::Cancelled => "Execution cancelled.",
+            Interrupt::OutOfMemory => "Out of memory.",
+        }
+    }
+}
+
+/// The bytes an engine has allocated. Environments and instances share it, so
+/// they can give their bytes back when they are dropped.
+pub(crate) type Allocated = Rc<Cell<usize>>;
+
+/// The bytes an environment or an instance accounts for.
+#[derive(Default)]
+pub(crate) struct Charge {
+    allocated: Option<Allocated>,
+    bytes: usize,
+}
+
+impl Charge {
+    /// Records that `charged` more and `released` fewer bytes were counted.
+    pub(crate) fn update(&mut self, allocated: &Allocated, released: usize, charged: usize) {
+        self.allocated.get_or_insert_with(|| allocated.clone());
+        self.bytes = self.bytes.saturating_sub(released) + charged;
+    }
+}
+
+impl Drop for Charge {
+    fn drop(&mut self) {
+        if let Some(allocated) = &self.allocated {
+            allocated.set(allocated.get().saturating_sub(self.bytes));
+        }
+    }
+}
+
+/// Roughly how many bytes binding `value` to `name` in an environment or an
+/// instance takes up.
+pub(crate) fn binding_size(name: &str, value: &Literal) -> usize {
+    let payload = match value {
+        Literal::StringLiteral(string) => string.len(),
+        _ => 0,
+    };
+    name.len() + mem::size_of::<Literal>() + payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lox, LoxError};
+
+    fn limited(max_memory: usize) -> Lox {
+        let mut lox = Lox::with_output(std::io::sink());
+        lox.set_limits(Limits {
+            max_memory: Some(max_memory),
+            ..Limits::default()
+        });
+        lox
+    }
+
+    /// What stops an endless loop, and the line it reports.
+    fn interrupt(limits: Limits) -> Option<(Interrupt, usize)> {
+        let mut lox = Lox::with_output(std::io::sink());
+        lox.set_limits(limits);
+        match lox.eval("var i = 0;\nwhile (true) {}") {
+            Err(LoxError::Interrupted(interrupt, diagnostic)) => Some((interrupt, diagnostic.line)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn endless_loop_runs_out_of_steps() {
+        let limits = Limits {
+            max_steps: Some(1000),
+            ..Limits::default()
+        };
+        assert_eq!(interrupt(limits), Some((Interrupt::StepLimit, 2)));
+    }
+
+    #[test]
+    fn endless_loop_runs_out_of_time() {
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(10)),
+            ..Limits::default()
+        };
+        assert_eq!(interrupt(limits), Some((Interrupt::Timeout, 2)));
+    }
+
+    #[test]
+    fn endless_loop_is_cancelled() {
+        let limits = Limits {
+            cancel: Some(Arc::new(AtomicBool::new(true))),
+            ..Limits::default()
+        };
+        // A flag set before the run stops it at its first statement.
+        assert_eq!(interrupt(limits), Some((Interrupt::Cancelled, 1)));
+    }
+
+    #[test]
+    fn dropped_instances_give_their_memory_back() {
+        let mut lox = limited(100_000);
+        let source = "
+            class P { init() { this.name = \"0123456789\"; } }
+            for (var i = 0; i < 20000; i = i + 1) P();";
+        assert_eq!(lox.eval(source), Ok(()));
+    }
+
+    #[test]
+    fn dropped_closures_give_their_environment_back() {
+        let mut lox = limited(100_000);
+        let source = "
+            fun mk() { var x = 1; fun g() { return x; } return g; }
+            for (var i = 0; i < 2000; i = i + 1) mk();";
+        assert_eq!(lox.eval(source), Ok(()));
+    }
+
+    #[test]
+    fn globals_count_across_runs() {
+        let mut lox = limited(100_000);
+        let string = "x".repeat(1000);
+        let result = (0..200)
+            .map(|i| lox.eval(&format!("var g{} = \"{}\";", i, string)))
+            .find(Result::is_err);
+        assert!(matches!(
+            result,
+            Some(Err(LoxError::Interrupted(Interrupt::OutOfMemory, _)))
+        ));
+    }
+
+    #[test]
+    fn deep_recursion_fails_on_a_small_thread() {
+        let result = std::thread::Builder::new()
+            .stack_size(1024 * 1024)
+            .spawn(|| Lox::new().eval("fun f(n) { return f(n + 1); } f(0);"))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(matches!(
+            result,
+            Err(LoxError::Runtime(diagnostic)) if diagnostic.message == "Stack overflow."
+        ));
+    }
+
+    #[test]
+    fn live_instances_run_out_of_memory() {
+        let mut lox = limited(100_000);
+        let source = "
+            class N { init(next) { this.next = next; } }
+            var list = nil;
+            for (var i = 0; i < 100000; i = i + 1) list = N(list);";
+        assert!(matches!(
+            lox.eval(source),
+            Err(LoxError::Interrupted(Interrupt::OutOfMemory, _))
+        ));
+    }
+}
@@ -0,0 +1,335 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    io::{self, Write},
+    rc::Rc,
+};
+
+use crate::{
+    conversion::{ConversionError, FromLox, IntoLox},
+    diagnostic::{Diagnostic, Diagnostics},
+    environment_tree::EnvironmentTree,
+    interpreter::{Interpreter, RuntimeErrorOrReturn},
+    limits::{Interrupt, Limits},
+    lox_callable::{Arity, LoxCallable, NativeClass, NativeFunction, NativeResult},
+    parser::Parser,
+    render::{self, DiagnosticFormat},
+    resolver::Resolver,
+    scanner::Scanner,
+    span::{FileId, SourceMap, Span},
+    stdlib::{self, Capabilities},
+    stmt::Stmt,
+    token::{Literal, Token},
+    token_type::TokenType,
+    trivia::{self, SyntaxToken},
+};
+
+/// An embeddable Lox engine.
+///
+/// Globals, functions and classes defined by one call to [`Lox::eval`] stay
+/// visible to the following calls, the same way they do in the REPL.
+pub struct Lox {
+    env: Rc<RefCell<EnvironmentTree>>,
+    interpreter: Interpreter,
+    id_index: usize,
+    sources: SourceMap,
+}
+
+/// Why a call to [`Lox::eval`] failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoxError {
+    /// The source could not be scanned, parsed or resolved, so nothing ran.
+    Compile(Vec<Diagnostic>),
+    /// Execution stopped on an uncaught runtime error.
+    Runtime(Box<Diagnostic>),
+    /// Execution stopped because it ran into one of the engine's [`Limits`].
+    Interrupted(Interrupt, Box<Diagnostic>),
+    /// A value returned to the host doesn't have the requested type.
+    Conversion(ConversionError),
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoxError::Compile(diagnostics) => {
+                for (i, diagnostic) in diagnostics.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            LoxError::Runtime(diagnostic) | LoxError::Interrupted(_, diagnostic) => {
+                write!(f, "{}", diagnostic)
+            }
+            LoxError::Conversion(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl LoxError {
+    /// The diagnostics behind the error; a conversion error has none.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            LoxError::Compile(diagnostics) => diagnostics,
+            LoxError::Runtime(diagnostic) | LoxError::Interrupted(_, diagnostic) => {
+                std::slice::from_ref(diagnostic.as_ref())
+            }
+            LoxError::Conversion(_) => &[],
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+/// How to set up a new engine.
+pub struct Config {
+    /// Where `print` statements write.
+    pub output: Box<dyn Write>,
+    /// Which built-in native functions scripts can call.
+    pub capabilities: Capabilities,
+    pub limits: Limits,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output: Box::new(io::stdout()),
+            capabilities: Capabilities::default(),
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl Lox {
+    /// Creates an engine with the default [`Config`]: output to stdout and only `clock`.
+    pub fn new() -> Self {
+        Self::with_config(Config::default())
+    }
+
+    /// Creates an engine whose `print` statements write to `output`.
+    pub fn with_output(output: impl Write + 'static) -> Self {
+        Self::with_config(Config {
+            output: Box::new(output),
+            ..Config::default()
+        })
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        let env = EnvironmentTree::new();
+        stdlib::install(&mut env.borrow_mut(), config.capabilities);
+        let mut interpreter = Interpreter::new(env.clone(), config.output);
+        interpreter.limits = config.limits;
+        Self {
+            env,
+            interpreter,
+            id_index: 0,
+            sources: SourceMap::default(),
+        }
+    }
+
+    /// Defines a global function `name` that runs `function` when a script calls it.
+    ///
+    /// The interpreter checks the argument count against `arity` before
+    /// `function` runs, so it only has to validate the argument types.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&[Literal]) -> NativeResult + 'static,
+    ) {
+        let native = NativeFunction::new(name, arity, function);
+        self.env.borrow_mut().define_global(
+            name.to_string(),
+            Literal::Callable(LoxCallable::Native(native)),
+        );
+    }
+
+    /// Defines a global class whose methods are implemented in Rust.
+    ///
+    /// Scripts can instantiate it and inherit from it like any other class.
+    pub fn register_class(&mut self, class: NativeClass) {
+        let name = class.name().to_string();
+        self.env
+            .borrow_mut()
+            .define_global(name, Literal::Callable(class.into_class()));
+    }
+
+    /// Replaces the budgets that apply to every following run.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.interpreter.limits = limits;
+    }
+
+    /// Reads the global variable `name`, converting it to `T`.
+    pub fn get_global<T: FromLox>(&self, name: &str) -> Result<T, LoxError> {
+        let value = self
+            .env
+            .borrow()
+            .get_global(&host_token(name))
+            .map_err(|err| LoxError::Runtime(Box::new(Diagnostic::from_runtime_error(&err))))?;
+        T::from_lox(&value).map_err(LoxError::Conversion)
+    }
+
+    /// Defines the global variable `name`, overwriting any previous value.
+    pub fn set_global(&mut self, name: &str, value: impl IntoLox) {
+        self.env
+            .borrow_mut()
+            .define_global(name.to_string(), value.into_lox());
+    }
+
+    /// Returns every global variable, including natives, sorted by name.
+    pub fn globals(&self) -> impl Iterator<Item = (String, Literal)> {
+        self.env.borrow().globals().into_iter()
+    }
+
+    /// Scans, parses, resolves and runs `source`.
+    pub fn eval(&mut self, source: &str) -> Result<(), LoxError> {
+        self.eval_named("<eval>", source)
+    }
+
+    /// Like [`Lox::eval`], but diagnostics refer to the source as `name`, usually its path.
+    pub fn eval_named(&mut self, name: &str, source: &str) -> Result<(), LoxError> {
+        let file = self.sources.add(name, source);
+        let mut diagnostics = Diagnostics::default();
+        let statements = parse(source, file, &mut self.id_index, &mut diagnostics);
+        if diagnostics.has_errors() {
+            return Err(LoxError::Compile(diagnostics.into_vec()));
+        }
+        let mut resolver = Resolver::new(self.env.clone(), &mut diagnostics);
+        resolver.resolve(&statements);
+        if diagnostics.has_errors() {
+            return Err(LoxError::Compile(diagnostics.into_vec()));
+        }
+        self.interpreter.start_run();
+        if self
+            .interpreter
+            .interpret(statements, &mut diagnostics)
+            .is_err()
+        {
+            let diagnostic = diagnostics.into_vec().pop().unwrap();
+            return Err(match self.interpreter.interrupt.take() {
+                Some(interrupt) => LoxError::Interrupted(interrupt, Box::new(diagnostic)),
+                None => LoxError::Runtime(Box::new(diagnostic)),
+            });
+        }
+        Ok(())
+    }
+
+    /// Reports every problem in `source` without running it, for editors and
+    /// linters. Unlike [`Lox::eval`], it doesn't stop at the first phase with
+    /// errors: declarations that fail to parse are skipped, and the rest is
+    /// still resolved.
+    ///
+    /// The engine keeps only the latest source checked under each name, for
+    /// [`Lox::render`] to quote.
+    pub fn check(&mut self, name: &str, source: &str) -> Vec<Diagnostic> {
+        let file = self.sources.replace(name, source);
+        let mut diagnostics = Diagnostics::default();
+        // Nothing that is only checked ever runs, so it needs neither the
+        // engine's ids nor its environment.
+        let statements = parse(source, file, &mut 0, &mut diagnostics);
+        let mut resolver = Resolver::new(EnvironmentTree::new(), &mut diagnostics);
+        resolver.resolve(&statements);
+        diagnostics.into_vec()
+    }
+
+    /// Splits `source` into tokens that keep its whitespace and comments, for
+    /// formatters and other tools. Scanning goes on past errors, and the
+    /// tokens cover all of `source` even when there are some. Like
+    /// [`Lox::check`], it keeps only the latest source under each name.
+    pub fn tokenize(&mut self, name: &str, source: &str) -> (Vec<SyntaxToken>, Vec<Diagnostic>) {
+        let file = self.sources.replace(name, source);
+        let mut diagnostics = Diagnostics::default();
+        let mut scanner = Scanner::new(source, file, &mut diagnostics);
+        scanner.keep_trivia = true;
+        scanner.scan_tokens();
+        let tokens = trivia::attach(source, file, scanner.tokens, scanner.trivia);
+        (tokens, diagnostics.into_vec())
+    }
+
+    /// Returns the text of a source passed to an earlier [`Lox::eval`]. The
+    /// engine keeps only the last 100 sources it was given.
+    pub fn source(&self, file: FileId) -> Option<&str> {
+        self.sources.text(file)
+    }
+
+    pub fn source_name(&self, file: FileId) -> Option<&str> {
+        self.sources.name(file)
+    }
+
+    /// Formats an error for people to read.
+    pub fn render(&self, error: &LoxError, format: DiagnosticFormat) -> String {
+        render::render(error, format, &self.sources)
+    }
+
+    /// Calls the global function or class `name` and converts what it returns.
+    pub fn call<R: FromLox>(&mut self, name: &str, arguments: Vec<Literal>) -> Result<R, LoxError> {
+        let callee: Literal = self.get_global(name)?;
+        self.call_value(&callee, arguments)
+    }
+
+    /// Calls a function or class value, such as one a script passed to a native function.
+    pub fn call_value<R: FromLox>(
+        &mut self,
+        callee: &Literal,
+        arguments: Vec<Literal>,
+    ) -> Result<R, LoxError> {
+        let token = host_token(&callee.stringify());
+        self.interpreter.start_run();
+        match self
+            .interpreter
+            .call_value(callee.clone(), arguments, &token)
+        {
+            Ok(result) => R::from_lox(&result).map_err(LoxError::Conversion),
+            Err(err) => Err(self.runtime_error(&err)),
+        }
+    }
+
+    /// Calls the method `name` on an instance.
+    pub fn call_method<R: FromLox>(
+        &mut self,
+        object: &Literal,
+        name: &str,
+        arguments: Vec<Literal>,
+    ) -> Result<R, LoxError> {
+        let token = host_token(name);
+        let method = self
+            .interpreter
+            .get_property(object.clone(), &token)
+            .map_err(|err| LoxError::Runtime(Box::new(Diagnostic::from_runtime_error(&err))))?;
+        self.call_value(&method, arguments)
+    }
+
+    fn runtime_error(&mut self, error: &RuntimeErrorOrReturn) -> LoxError {
+        let diagnostic = self.interpreter.diagnose(error);
+        match self.interpreter.interrupt.take() {
+            Some(interrupt) => LoxError::Interrupted(interrupt, Box::new(diagnostic)),
+            None => LoxError::Runtime(Box::new(diagnostic)),
+        }
+    }
+}
+
+fn parse(source: &str, file: FileId, ids: &mut usize, diagnostics: &mut Diagnostics) -> Vec<Stmt> {
+    let mut scanner = Scanner::new(source, file, diagnostics);
+    scanner.scan_tokens();
+    let tokens = scanner.tokens;
+    let mut parser = Parser::from(tokens, ids, diagnostics);
+    parser.parse()
+}
+
+/// Stands in for a call-site token when the host, not a script, makes the call.
+fn host_token(name: &str) -> Token {
+    Token::from(
+        TokenType::Identifier,
+        name.to_string(),
+        Literal::NoneLiteral,
+        Span::at_line(0),
+    )
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,33 +1,153 @@
 use std::{
     cell::RefCell,
     collections::HashMap,
-    rc::Rc,
-    time::{SystemTime, UNIX_EPOCH},
+    fmt, mem,
+    rc::{Rc, Weak},
 };
 
 use crate::{
     environment_tree::{EnvironmentNode, EnvironmentTree},
-    interpreter::ExprVisitorResult,
+    error_code::ErrorCode,
+    interpreter::{ExprVisitorResult, RuntimeErrorOrReturn},
+    limits::binding_size,
     lox_instance::LoxInstance,
-    stmt,
+    token::Token,
 };
 use crate::{interpreter::Interpreter, stmt::Function, token::Literal};
 #[derive(Clone)]
-pub(crate) enum LoxCallable {
+pub enum LoxCallable {
     UserFunction(LoxFunction),
     UserClass(Rc<RefCell<LoxClass>>),
-    Clock,
+    Native(Rc<NativeFunction>),
+}
+
+/// How many arguments a callable accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Fixed(usize),
+    /// Variadic, with at least this many arguments.
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub(crate) fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Fixed(arity) => count == *arity,
+            Arity::AtLeast(minimum) => count >= *minimum,
+        }
+    }
+
+    pub(crate) fn mismatch_message(&self, count: usize) -> String {
+        match self {
+            Arity::Fixed(arity) => format!("Expected {} arguments but got {}.", arity, count),
+            Arity::AtLeast(minimum) => {
+                format!("Expected at least {} arguments but got {}.", minimum, count)
+            }
+        }
+    }
+}
+
+/// The error a native function fails with; it becomes a runtime error at the call site.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeError {
+    pub message: String,
+}
+
+impl From<String> for NativeError {
+    fn from(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl From<&str> for NativeError {
+    fn from(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+        }
+    }
+}
+
+pub type NativeResult = Result<Literal, NativeError>;
+
+type NativeFn = dyn Fn(&[Literal]) -> NativeResult;
+
+/// A function implemented in Rust and callable from Lox.
+pub struct NativeFunction {
+    pub(crate) name: String,
+    pub(crate) arity: Arity,
+    pub(crate) function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub(crate) fn new(
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&[Literal]) -> NativeResult + 'static,
+    ) -> Rc<Self> {
+        Rc::new(Self {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        })
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
 }
 
 #[derive(Clone)]
-pub(crate) struct LoxFunction {
+pub struct LoxFunction {
     pub(crate) declaration: Rc<Function>,
-    pub(crate) closure: Rc<RefCell<EnvironmentNode>>,
+    pub(crate) closure: Closure,
     pub(crate) is_initializer: bool,
 }
+
+/// The environment a function closes over.
+#[derive(Clone)]
+pub(crate) enum Closure {
+    Strong(Rc<RefCell<EnvironmentNode>>),
+    /// While the function is bound in that environment itself, so that the
+    /// two don't keep each other alive.
+    Weak(Weak<RefCell<EnvironmentNode>>),
+}
+
 impl LoxFunction {
+    pub(crate) fn closure(&self) -> Rc<RefCell<EnvironmentNode>> {
+        match &self.closure {
+            Closure::Strong(closure) => closure.clone(),
+            Closure::Weak(closure) => closure
+                .upgrade()
+                .expect("a function outlived the environment it is bound in"),
+        }
+    }
+
+    /// The function to bind in `environment`.
+    pub(crate) fn stored_in(self, environment: &Rc<RefCell<EnvironmentNode>>) -> Self {
+        match &self.closure {
+            Closure::Strong(closure) if Rc::ptr_eq(closure, environment) => Self {
+                closure: Closure::Weak(Rc::downgrade(closure)),
+                ..self
+            },
+            _ => self,
+        }
+    }
+
+    /// The function as read back from the environment it is bound in.
+    pub(crate) fn loaded(&self) -> Self {
+        Self {
+            closure: Closure::Strong(self.closure()),
+            ..self.clone()
+        }
+    }
+
     pub(crate) fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> Literal {
-        let environment = EnvironmentTree::new_environment_node(Some(self.closure.clone()));
+        let environment = EnvironmentTree::new_environment_node(Some(self.closure()));
         EnvironmentNode::define(
             environment.clone(),
             "this".to_string(),
@@ -35,47 +155,137 @@ impl LoxFunction {
         );
         Literal::Callable(LoxCallable::UserFunction(LoxFunction {
             declaration: self.declaration.clone(),
-            closure: environment,
+            closure: Closure::Strong(environment),
             is_initializer: self.is_initializer,
         }))
     }
 }
+type NativeMethodFn = dyn Fn(&Rc<RefCell<LoxInstance>>, &[Literal]) -> NativeResult;
+
+/// A method implemented in Rust; it receives the instance it was called on.
+pub struct NativeMethod {
+    pub(crate) name: String,
+    pub(crate) arity: Arity,
+    pub(crate) method: Box<NativeMethodFn>,
+}
+
+impl NativeMethod {
+    fn bind(method: &Rc<NativeMethod>, instance: Rc<RefCell<LoxInstance>>) -> Literal {
+        let method = method.clone();
+        let native = NativeFunction::new(&method.name.clone(), method.arity, move |arguments| {
+            (method.method)(&instance, arguments)
+        });
+        Literal::Callable(LoxCallable::Native(native))
+    }
+}
+
 #[derive(Clone)]
-pub(crate) struct LoxClass {
-    pub(crate) class: Rc<stmt::Class>,
+pub(crate) enum Method {
+    User(LoxFunction),
+    Native(Rc<NativeMethod>),
+}
+
+impl Method {
+    pub(crate) fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> Literal {
+        match self {
+            Method::User(function) => function.bind(instance),
+            Method::Native(method) => NativeMethod::bind(method, instance),
+        }
+    }
+
+    pub(crate) fn arity(&self) -> Arity {
+        match self {
+            Method::User(function) => Arity::Fixed(function.declaration.params.len()),
+            Method::Native(method) => method.arity,
+        }
+    }
+}
+
+/// A class implemented in Rust, registered with [`crate::Lox::register_class`].
+///
+/// The `init` method is the constructor. It usually attaches the host data
+/// the other methods work on with [`LoxInstance::set_host_data`].
+pub struct NativeClass {
+    name: String,
+    methods: HashMap<String, Method>,
+}
+
+impl NativeClass {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            methods: HashMap::new(),
+        }
+    }
+
+    pub fn init(
+        self,
+        arity: Arity,
+        init: impl Fn(&Rc<RefCell<LoxInstance>>, &[Literal]) -> NativeResult + 'static,
+    ) -> Self {
+        self.method("init", arity, init)
+    }
+
+    pub fn method(
+        mut self,
+        name: &str,
+        arity: Arity,
+        method: impl Fn(&Rc<RefCell<LoxInstance>>, &[Literal]) -> NativeResult + 'static,
+    ) -> Self {
+        let method = NativeMethod {
+            name: name.to_string(),
+            arity,
+            method: Box::new(method),
+        };
+        self.methods
+            .insert(name.to_string(), Method::Native(Rc::new(method)));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn into_class(self) -> LoxCallable {
+        LoxClass::new(self.name, None, self.methods)
+    }
+}
+
+#[derive(Clone)]
+pub struct LoxClass {
+    pub(crate) name: String,
     pub(crate) superclass: Option<Rc<RefCell<LoxClass>>>,
-    pub(crate) methods: HashMap<String, Literal>,
+    pub(crate) methods: HashMap<String, Method>,
 }
 
 impl LoxClass {
+    #[allow(clippy::new_ret_no_self)]
     pub(crate) fn new(
-        class: Rc<stmt::Class>,
+        name: String,
         superclass: Option<Rc<RefCell<LoxClass>>>,
-        methods: HashMap<String, Literal>,
+        methods: HashMap<String, Method>,
     ) -> LoxCallable {
         LoxCallable::UserClass(Rc::new(RefCell::new(Self {
-            class,
+            name,
             superclass,
             methods,
         })))
     }
-    pub(crate) fn find_method(&self, name: &str) -> Option<LoxFunction> {
-        if let Some(Literal::Callable(LoxCallable::UserFunction(method))) = self.find_method_wrapped_in_literal(name) {
-            Some(method.clone())
-        } else {
-            None
+
+    /// Every method an instance can call, including inherited ones.
+    pub(crate) fn method_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.methods.keys().cloned().collect();
+        if let Some(superclass) = &self.superclass {
+            names.extend(superclass.borrow().method_names());
         }
+        names
     }
 
-    pub(crate) fn find_method_wrapped_in_literal(&self, name: &str) -> Option<Literal> {
-        if let Some(literal) = self.methods.get(name) {
-            Some(literal.clone())
+    pub(crate) fn find_method(&self, name: &str) -> Option<Method> {
+        if let Some(method) = self.methods.get(name) {
+            Some(method.clone())
         } else if let Some(superclass) = &self.superclass {
-            if let Some(literal) = superclass.borrow().find_method_wrapped_in_literal(name) {
-                Some(literal.clone())
-            } else {
-                None
-            }
+            superclass.borrow().find_method(name)
         } else {
             None
         }
@@ -87,29 +297,33 @@ impl LoxCallable {
         &self,
         interpreter: &mut Interpreter,
         arguments: Vec<Literal>,
+        paren: &Token,
     ) -> ExprVisitorResult {
         match self {
-            LoxCallable::Clock => Ok(Literal::Float(
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs_f64(),
-            )),
+            LoxCallable::Native(native) => {
+                (native.function)(&arguments).map_err(|err| RuntimeErrorOrReturn {
+                    message: err.message.into(),
+                    code: Some(ErrorCode::NativeFailure),
+                    token: paren.clone(),
+                    return_flag: false,
+                    help: None,
+                })
+            }
             LoxCallable::UserFunction(fun) => {
-                let new_env_node = EnvironmentTree::new_environment_node(Some(fun.closure.clone()));
-                for i in 0..fun.declaration.params.len() {
-                    (*new_env_node).borrow_mut().environment.insert(
-                        fun.declaration.params[i].lexeme.clone(),
-                        arguments[i].clone(),
-                    );
+                let new_env_node = EnvironmentTree::new_environment_node(Some(fun.closure()));
+                for (param, argument) in fun.declaration.params.iter().zip(arguments) {
+                    let size = binding_size(&param.lexeme, &argument);
+                    interpreter.account(&mut new_env_node.borrow_mut().charge, 0, size)?;
+                    (*new_env_node)
+                        .borrow_mut()
+                        .environment
+                        .insert(param.lexeme.clone(), argument);
                 }
-                if let Err(err) =
-                    interpreter.execute_block(&fun.declaration.body, new_env_node.clone())
-                {
+                if let Err(err) = interpreter.execute_block(&fun.declaration.body, new_env_node) {
                     if err.return_flag {
                         if fun.is_initializer {
                             Ok(fun
-                                .closure
+                                .closure()
                                 .borrow()
                                 .environment
                                 .get("this")
@@ -123,7 +337,7 @@ impl LoxCallable {
                     }
                 } else if fun.is_initializer {
                     Ok(fun
-                        .closure
+                        .closure()
                         .borrow()
                         .environment
                         .get("this")
@@ -135,11 +349,13 @@ impl LoxCallable {
             }
             LoxCallable::UserClass(class) => {
                 let lox_inst = LoxInstance::new(class.clone());
+                let size = mem::size_of::<LoxInstance>();
+                interpreter.account(&mut lox_inst.borrow_mut().charge, 0, size)?;
                 let initializer = class.borrow().find_method("init");
-                if let Some(lox_function) = initializer {
-                    let bounded = lox_function.bind(lox_inst.clone());
+                if let Some(method) = initializer {
+                    let bounded = method.bind(lox_inst.clone());
                     if let Literal::Callable(calleable) = bounded {
-                        return calleable.call(interpreter, arguments);
+                        calleable.call(interpreter, arguments, paren)?;
                     }
                 }
                 Ok(Literal::Instance(lox_inst))
@@ -147,12 +363,29 @@ impl LoxCallable {
         }
     }
 
+    /// How the callable appears in a stack trace.
+    pub(crate) fn name(&self) -> String {
+        match self {
+            LoxCallable::Native(native) => native.name.clone(),
+            LoxCallable::UserFunction(fun) => {
+                let name = &fun.declaration.name.lexeme;
+                match fun.closure().borrow().environment.get("this") {
+                    Some(Literal::Instance(instance)) => {
+                        format!("{}.{}", instance.borrow().class_name(), name)
+                    }
+                    _ => name.clone(),
+                }
+            }
+            LoxCallable::UserClass(class) => format!("{}.init", class.borrow().name),
+        }
+    }
+
     pub(crate) fn stringify(&self) -> String {
         match self {
-            LoxCallable::Clock => "<native fn>".to_string(),
+            LoxCallable::Native(_) => "<native fn>".to_string(),
             LoxCallable::UserFunction(fun) => format!("<fn {}>", &fun.declaration.name.lexeme),
             LoxCallable::UserClass(class) => {
-                let mut cur = format!("<cls {}>", &class.borrow().class.name.lexeme);
+                let mut cur = format!("<cls {}>", &class.borrow().name);
                 if let Some(superclass) = &class.borrow().superclass {
                     cur.push_str(" extends ");
                     cur.push_str(&LoxCallable::UserClass(superclass.clone()).stringify());
@@ -162,16 +395,16 @@ impl LoxCallable {
         }
     }
 
-    pub(crate) fn arity(&self) -> usize {
+    pub(crate) fn arity(&self) -> Arity {
         match self {
-            LoxCallable::Clock => 0,
-            LoxCallable::UserFunction(fun) => fun.declaration.params.len(),
+            LoxCallable::Native(native) => native.arity,
+            LoxCallable::UserFunction(fun) => Arity::Fixed(fun.declaration.params.len()),
             LoxCallable::UserClass(class) => {
                 let initializer = class.borrow().find_method("init");
-                if let Some(lox_function) = initializer {
-                    lox_function.declaration.params.len()
+                if let Some(method) = initializer {
+                    method.arity()
                 } else {
-                    0
+                    Arity::Fixed(0)
                 }
             }
         }
@@ -1,14 +1,20 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{any::Any, cell::RefCell, collections::HashMap, rc::Rc};
 
 use crate::{
+    error_code::ErrorCode,
     interpreter::{ExprVisitorResult, RuntimeErrorOrReturn},
+    limits::Charge,
     lox_callable::LoxClass,
+    suggest,
     token::{Literal, Token},
 };
 
-pub(crate) struct LoxInstance {
+pub struct LoxInstance {
     class: Rc<RefCell<LoxClass>>,
     fields: HashMap<String, Literal>,
+    host_data: Option<Box<dyn Any>>,
+    /// What the instance and its fields count against the memory limit.
+    pub(crate) charge: Charge,
 }
 
 impl LoxInstance {
@@ -16,11 +22,13 @@ impl LoxInstance {
         Rc::new(RefCell::new(Self {
             class,
             fields: HashMap::new(),
+            host_data: None,
+            charge: Charge::default(),
         }))
     }
 
     pub(crate) fn stringify(&self) -> String {
-        format!("<cls {}> instance", self.class.borrow().class.name.lexeme).to_string()
+        format!("<cls {}> instance", self.class.borrow().name)
     }
 
     pub(crate) fn get(
@@ -34,14 +42,48 @@ impl LoxInstance {
             Ok(method.bind(self_rc_rfc))
         } else {
             Err(RuntimeErrorOrReturn {
-                message: format!("Undefined property '{}'.", &name.lexeme),
+                message: format!("Undefined property '{}'.", name.lexeme).into(),
+                code: Some(ErrorCode::UndefinedProperty),
                 token: name.clone(),
                 return_flag: false,
+                help: suggest::did_you_mean(
+                    &name.lexeme,
+                    self.fields
+                        .keys()
+                        .chain(&self.class.borrow().method_names())
+                        .map(String::as_str),
+                ),
             })
         }
     }
 
-    pub(crate) fn set(&mut self, name: &Token, value: Literal) {
-        self.fields.insert(name.lexeme.clone(), value);
+    pub(crate) fn set(&mut self, name: &Token, value: Literal) -> Option<Literal> {
+        self.fields.insert(name.lexeme.clone(), value)
+    }
+
+    pub fn class_name(&self) -> String {
+        self.class.borrow().name.clone()
+    }
+
+    pub fn get_field(&self, name: &str) -> Option<Literal> {
+        self.fields.get(name).cloned()
+    }
+
+    pub fn set_field(&mut self, name: &str, value: Literal) {
+        self.fields.insert(name.to_string(), value);
+    }
+
+    /// Returns the host data attached by a native class, if it has type `T`.
+    pub fn host_data<T: Any>(&self) -> Option<&T> {
+        self.host_data.as_ref()?.downcast_ref()
+    }
+
+    pub fn host_data_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.host_data.as_mut()?.downcast_mut()
+    }
+
+    /// Attaches opaque Rust data to the instance, replacing any previous data.
+    pub fn set_host_data<T: Any>(&mut self, data: T) {
+        self.host_data = Some(Box::new(data));
     }
 }
@@ -1,44 +1,65 @@
-use environment_tree::EnvironmentTree;
-use interpreter::{Interpreter, RuntimeErrorOrReturn};
-use lox_callable::LoxCallable;
-use parser::Parser;
-use resolver::Resolver;
-use scanner::Scanner;
-use std::cell::RefCell;
+use rlox::{Capabilities, Config, DiagnosticFormat, ErrorCode, Limits, Lox, LoxError};
 use std::env;
 use std::fs::File;
 use std::io::{Read, Write};
-use std::rc::Rc;
-use token::{Literal, Token};
 
-use crate::token_type::TokenType;
-mod environment_tree;
-mod expr;
-mod interpreter;
-mod lox_callable;
-mod lox_instance;
-mod parser;
-mod resolver;
-mod scanner;
-mod stmt;
-mod token;
-mod token_type;
+/// Scripts run on a thread with this much stack, so they can recurse as
+/// deeply as they would in jlox.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
-static mut HAD_ERROR: bool = false;
-static mut HAD_RUNTIME_ERROR: bool = false;
+/// The part of the stack the host keeps for itself.
+const HOST_STACK: usize = 2 * 1024 * 1024;
+
+const USAGE: &str =
+    "Usage: rlox [--error-format=short|pretty|json] [script].\n       rlox explain <code>.";
 
 fn main() {
-    let mut current: usize = 0;
     let args: Vec<_> = env::args().collect();
-    if args.len() > 2 {
-        println!("Usage: rlox [script].");
-    } else if args.len() == 2 {
-        run_file(&args[1], &mut current);
-    } else {
-        run_prompt(&mut current);
+    if args.get(1).map(String::as_str) == Some("explain") {
+        match args.get(2).and_then(|code| ErrorCode::parse(code)) {
+            Some(code) if args.len() == 3 => print!("{}\n\n{}", code, code.explanation()),
+            Some(_) => println!("{}", USAGE),
+            None => {
+                eprintln!(
+                    "Unknown error code: {}.",
+                    args.get(2).map_or("", String::as_str)
+                );
+                std::process::exit(64);
+            }
+        }
+        return;
+    }
+    let mut format = DiagnosticFormat::Short;
+    let mut scripts = Vec::new();
+    for arg in args.into_iter().skip(1) {
+        if !arg.starts_with("--") {
+            scripts.push(arg);
+            continue;
+        }
+        format = match arg.strip_prefix("--error-format=") {
+            Some("short") => DiagnosticFormat::Short,
+            Some("pretty") => DiagnosticFormat::Pretty,
+            Some("json") => DiagnosticFormat::Json,
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(64);
+            }
+        };
+    }
+    let interpreter = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || match scripts.as_slice() {
+            [] => run_prompt(format),
+            [script] => run_file(script, format),
+            _ => println!("{}", USAGE),
+        })
+        .expect("failed to start the interpreter thread");
+    if interpreter.join().is_err() {
+        std::process::exit(101);
     }
 }
-fn run_file(file_name: &str, current: &mut usize) {
+
+fn run_file(file_name: &str, format: DiagnosticFormat) {
     let mut file = match File::open(file_name) {
         Ok(file_handle) => file_handle,
         Err(error) => panic!(
@@ -48,105 +69,46 @@ fn run_file(file_name: &str, current: &mut usize) {
     };
     let mut file_contents = String::new();
     file.read_to_string(&mut file_contents)
-        .expect(&format!("Error reading the file: {file_name}."));
+        .unwrap_or_else(|_| panic!("Error reading the file: {file_name}."));
 
-    let env = get_env();
-    let mut interpreter = Interpreter {
-        return_value: None,
-        env: env.clone(),
-    };
-
-    run(&file_contents, &mut interpreter, current, env);
-    unsafe {
-        if HAD_ERROR {
-            std::process::exit(65);
-        }
-        if HAD_RUNTIME_ERROR {
-            std::process::exit(70);
+    let mut lox = new_lox();
+    if let Err(error) = lox.eval_named(file_name, &file_contents) {
+        eprintln!("{}", lox.render(&error, format));
+        match error {
+            LoxError::Compile(_) => std::process::exit(65),
+            _ => std::process::exit(70),
         }
     }
 }
 
-fn run(
-    source: &str,
-    interpreter: &mut Interpreter,
-    current: &mut usize,
-    env: Rc<RefCell<EnvironmentTree>>,
-) {
-    let mut scanner = Scanner::new(source);
-    scanner.scan_tokens();
-    let mut parser = Parser::from(scanner.tokens, current);
-    match parser.parse() {
-        Ok(statements) => {
-            let mut resolver = Resolver::new(env.clone());
-            resolver.resolve(&statements);
-            unsafe {
-                if HAD_ERROR {
-                    return;
-                }
-            }
-            match interpreter.interpret(statements) {
-                Err(_) => (), //println!("Runtime error."),
-                _ => (),
-            }
-        }
-        Err(_) => (), //println!("Parse error."),
-    }
-}
-
-fn run_prompt(current: &mut usize) {
+fn run_prompt(format: DiagnosticFormat) {
     let stdin = std::io::stdin();
-    let env = get_env();
-    let mut interpreter = Interpreter {
-        return_value: None,
-        env: env.clone(),
-    };
+    let mut lox = new_lox();
 
     loop {
         print!("> ");
-        _ = std::io::stdout().flush().unwrap();
+        std::io::stdout().flush().unwrap();
         let mut buffer = String::new();
         match stdin.read_line(&mut buffer) {
             Ok(0) => break,
-            Ok(_) => _ = run(&buffer, &mut interpreter, current, env.clone()),
+            Ok(_) => {
+                if let Err(error) = lox.eval_named("<stdin>", &buffer) {
+                    eprintln!("{}", lox.render(&error, format));
+                }
+            }
             Err(error) => println!("error: {error}"),
         }
-        unsafe {
-            HAD_ERROR = false;
-        }
-    }
-}
-
-pub(crate) fn error(line: usize, message: &str) {
-    report(line, "", message);
-}
-
-pub(crate) fn report(line: usize, location: &str, message: &str) {
-    eprintln!("[line {}] Error{}: {}", line, location, message);
-    unsafe {
-        HAD_ERROR = true;
-    }
-}
-
-pub(crate) fn error_with_token(token: &Token, message: &str) {
-    if token.token_type == TokenType::Eof {
-        report(token.line, " at end", message);
-    } else {
-        report(token.line, &format!(" at '{}'", &token.lexeme), message);
-    }
-}
-
-pub(crate) fn runtime_error(error: &RuntimeErrorOrReturn) {
-    eprintln!("{}\n[line {}]", error.message, error.token.line);
-    unsafe {
-        HAD_RUNTIME_ERROR = true;
     }
 }
 
-fn get_env() -> Rc<RefCell<EnvironmentTree>> {
-    let env_tree = EnvironmentTree::new();
-    env_tree
-        .borrow_mut()
-        .define_global("clock".to_string(), Literal::Callable(LoxCallable::Clock));
-    env_tree
+fn new_lox() -> Lox {
+    Lox::with_config(Config {
+        capabilities: Capabilities::all(),
+        limits: Limits {
+            max_call_depth: usize::MAX,
+            max_stack: STACK_SIZE - HOST_STACK,
+            ..Limits::default()
+        },
+        ..Config::default()
+    })
 }
@@ -1,16 +1,16 @@
 use std::rc::Rc;
 
 use crate::expr::Expr::{
-    AssignExpr, BinaryExpr, CallExpr, GetExpr, GroupingExpr, LiteralExprExpr, LogicalExpr, SetExpr,
-    SuperExpr, ThisExpr, UnaryExpr, VariableExpr,
+    AssignExpr, BinaryExpr, CallExpr, GetExpr, GroupingExpr, InterpolationExpr, LiteralExprExpr,
+    LogicalExpr, SetExpr, SuperExpr, ThisExpr, UnaryExpr, VariableExpr,
 };
 use crate::expr::{
-    Assign, Binary, Call, Expr, Get, Grouping, LiteralExpr, Logical, Set, Super, This, Unary,
-    Variable,
+    Assign, Binary, Call, Expr, Get, Grouping, Interpolation, LiteralExpr, Logical, Set, Super,
+    This, Unary, Variable,
 };
 use crate::stmt::Stmt::{
-    BlockStmt, ClassStmt, ExpressionStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt, VarStmt,
-    WhileStmt,
+    BlockStmt, ClassStmt, ErrorStmt, ExpressionStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt,
+    VarStmt, WhileStmt,
 };
 use crate::stmt::{Block, Expression, Function, If, Print, Return, Stmt, Var, While};
 use crate::token::{
@@ -18,12 +18,17 @@ use crate::token::{
     Token,
 };
 use crate::token_type::TokenType::{self, *};
-use crate::{error_with_token, stmt};
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics, Phase},
+    error_code::ErrorCode,
+    stmt,
+};
 
 pub(crate) struct Parser<'a> {
     tokens: Vec<Token>,
     id_index: &'a mut usize,
     current: usize,
+    diagnostics: &'a mut Diagnostics,
 }
 
 pub(crate) struct ParseError;
@@ -31,27 +36,45 @@ type ExprResult = Result<Expr, ParseError>;
 type StmtResult = Result<Stmt, ParseError>;
 type ParseResult = Result<Vec<Stmt>, ParseError>;
 impl<'a> Parser<'a> {
-    pub(crate) fn from(tokens: Vec<Token>, id_index: &'a mut usize) -> Self {
+    pub(crate) fn from(
+        tokens: Vec<Token>,
+        id_index: &'a mut usize,
+        diagnostics: &'a mut Diagnostics,
+    ) -> Self {
         Self {
             tokens,
             id_index,
             current: 0,
+            diagnostics,
         }
     }
 
-    pub(crate) fn parse(&mut self) -> ParseResult {
+    /// Parses every declaration, putting an error node in place of each one
+    /// that fails, so the result is complete even when there are errors.
+    pub(crate) fn parse(&mut self) -> Vec<Stmt> {
         let mut statements = Vec::new();
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            statements.push(self.declaration());
         }
-        Ok(statements)
+        statements
     }
 
     fn expression(&mut self) -> ExprResult {
         self.assignment()
     }
 
-    fn declaration(&mut self) -> StmtResult {
+    fn declaration(&mut self) -> Stmt {
+        let start = self.current;
+        match self.declaration_or_error() {
+            Ok(stmt) => stmt,
+            Err(_) => {
+                self.synchronize();
+                ErrorStmt(stmt::Error::new(self.tokens[start..self.current].to_vec()))
+            }
+        }
+    }
+
+    fn declaration_or_error(&mut self) -> StmtResult {
         if self.match_next_token_type(vec![Class]) {
             return self.class_declaration();
         }
@@ -61,16 +84,7 @@ impl<'a> Parser<'a> {
         if self.match_next_token_type(vec![Var]) {
             return self.var_declaration();
         }
-        let stmt_result = self.statement();
-        match stmt_result {
-            Err(_) => {
-                self.synchronize();
-                Ok(Stmt::ExpressionStmt(Expression::new(
-                    Expr::LiteralExprExpr(LiteralExpr::new(NoneLiteral)),
-                )))
-            }
-            _ => stmt_result,
-        }
+        self.statement()
     }
 
     fn class_declaration(&mut self) -> StmtResult {
@@ -110,23 +124,22 @@ impl<'a> Parser<'a> {
     }
 
     fn for_statement(&mut self) -> StmtResult {
+        let keyword = self.previous();
         self.consume(LeftParen, "Expect '(' after 'for'.")?;
 
-        let initializer: Option<Stmt>;
-        if self.match_next_token_type(vec![Semicolon]) {
-            initializer = None;
+        let initializer = if self.match_next_token_type(vec![Semicolon]) {
+            None
         } else if self.match_next_token_type(vec![Var]) {
-            initializer = Some(self.var_declaration()?);
+            Some(self.var_declaration()?)
         } else {
-            initializer = Some(self.expression_statement()?);
-        }
+            Some(self.expression_statement()?)
+        };
 
-        let condition: Expr;
-        if !self.check_type(Semicolon) {
-            condition = self.expression()?;
+        let condition = if !self.check_type(Semicolon) {
+            self.expression()?
         } else {
-            condition = LiteralExprExpr(LiteralExpr::new(BoolLiteral(true)));
-        }
+            LiteralExprExpr(LiteralExpr::new(BoolLiteral(true)))
+        };
         self.consume(Semicolon, "Expect ';' after loop condition.")?;
 
         let mut increment = None;
@@ -144,7 +157,7 @@ impl<'a> Parser<'a> {
             ]));
         }
 
-        body = WhileStmt(While::new(condition, body));
+        body = WhileStmt(While::new(keyword, condition, body));
 
         if let Some(init_stmt) = initializer {
             body = BlockStmt(Block::new(vec![init_stmt, body]));
@@ -154,6 +167,7 @@ impl<'a> Parser<'a> {
     }
 
     fn if_statement(&mut self) -> StmtResult {
+        let keyword = self.previous();
         self.consume(LeftParen, "Expect '(' after 'if'.")?;
         let condition = self.expression()?;
         self.consume(RightParen, "Expect ')' after if condition.")?;
@@ -162,31 +176,37 @@ impl<'a> Parser<'a> {
         if self.match_next_token_type(vec![Else]) {
             else_branch = Some(self.statement()?);
         }
-        Ok(IfStmt(If::new(condition, then_branch, else_branch)))
+        Ok(IfStmt(If::new(
+            keyword,
+            condition,
+            then_branch,
+            else_branch,
+        )))
     }
 
     fn while_statement(&mut self) -> StmtResult {
+        let keyword = self.previous();
         self.consume(LeftParen, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
         self.consume(RightParen, "Expect ')' after while condition.")?;
         let body = self.statement()?;
-        Ok(WhileStmt(While::new(condition, body)))
+        Ok(WhileStmt(While::new(keyword, condition, body)))
     }
 
     fn print_statement(&mut self) -> StmtResult {
+        let keyword = self.previous();
         let value = self.expression()?;
         self.consume(Semicolon, "Expect ';' after value.")?;
-        Ok(PrintStmt(Print::new(value)))
+        Ok(PrintStmt(Print::new(keyword, value)))
     }
 
     fn return_statement(&mut self) -> StmtResult {
         let keyword = self.previous();
-        let value: Expr;
-        if !self.check_type(Semicolon) {
-            value = self.expression()?
+        let value = if !self.check_type(Semicolon) {
+            self.expression()?
         } else {
-            value = LiteralExprExpr(LiteralExpr::new(NoneLiteral));
-        }
+            LiteralExprExpr(LiteralExpr::new(NoneLiteral))
+        };
         self.consume(Semicolon, "Expect ';' after return value.")?;
         Ok(ReturnStmt(Return::new(keyword, value)))
     }
@@ -215,7 +235,11 @@ impl<'a> Parser<'a> {
             loop {
                 if parameters.len() >= 255 {
                     let err_tok = self.peek().clone();
-                    self.error(&err_tok, "Can't have more than 255 parameters.");
+                    self.error(
+                        &err_tok,
+                        ErrorCode::TooManyParameters,
+                        "Can't have more than 255 parameters.",
+                    );
                 }
                 parameters.push(self.consume(Identifier, "Expect parameter name.")?);
 
@@ -224,7 +248,7 @@ impl<'a> Parser<'a> {
                 }
             }
         }
-        self.consume(RightParen, &format!("Expect ')' after parameters."))?;
+        self.consume(RightParen, "Expect ')' after parameters.")?;
 
         self.consume(LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
         let body = self.block()?;
@@ -234,7 +258,7 @@ impl<'a> Parser<'a> {
     fn block(&mut self) -> ParseResult {
         let mut statements = Vec::new();
         while !self.check_type(RightBrace) && !self.is_at_end() {
-            statements.push(self.declaration()?)
+            statements.push(self.declaration())
         }
         self.consume(RightBrace, "Expect '}' after block.")?;
         Ok(statements)
@@ -255,7 +279,11 @@ impl<'a> Parser<'a> {
                     )))
                 }
                 GetExpr(get_expr) => Ok(SetExpr(Set::new(get_expr.object, get_expr.name, value))),
-                _ => Err(self.error(&equals, "Invalid assignment target.")),
+                _ => Err(self.error(
+                    &equals,
+                    ErrorCode::InvalidAssignmentTarget,
+                    "Invalid assignment target.",
+                )),
             }
         } else {
             Ok(expr)
@@ -299,7 +327,7 @@ impl<'a> Parser<'a> {
                 return true;
             }
         }
-        return false;
+        false
     }
 
     fn check_type(&mut self, token_type: TokenType) -> bool {
@@ -314,7 +342,7 @@ impl<'a> Parser<'a> {
         if !self.is_at_end() {
             self.current += 1;
         }
-        return self.previous();
+        self.previous()
     }
 
     fn is_at_end(&self) -> bool {
@@ -392,7 +420,11 @@ impl<'a> Parser<'a> {
         if !self.check_type(RightParen) {
             loop {
                 if arguments.len() >= 255 {
-                    self.error(&self.peek().clone(), "Can't have more than 255 arguments.");
+                    self.error(
+                        &self.peek().clone(),
+                        ErrorCode::TooManyArguments,
+                        "Can't have more than 255 arguments.",
+                    );
                 }
                 arguments.push(self.expression()?);
                 if !self.match_next_token_type(vec![Comma]) {
@@ -416,9 +448,12 @@ impl<'a> Parser<'a> {
         if self.match_next_token_type(vec![NilTokenType]) {
             return Ok(LiteralExprExpr(LiteralExpr::new(NoneLiteral)));
         }
-        if self.match_next_token_type(vec![Number, StringToken]) {
+        if !self.continues_string() && self.match_next_token_type(vec![Number, StringToken]) {
             return Ok(LiteralExprExpr(LiteralExpr::new(self.previous().literal)));
         }
+        if !self.continues_string() && self.match_next_token_type(vec![StringInterpolation]) {
+            return self.interpolation();
+        }
         if self.match_next_token_type(vec![Super]) {
             let keyword = self.previous();
             self.consume(Dot, "Expect '.' after 'super'.")?;
@@ -439,20 +474,65 @@ impl<'a> Parser<'a> {
             self.consume(RightParen, "Expect ')' after expression.")?;
             return Ok(GroupingExpr(Grouping::new(expr)));
         }
-        error_with_token(self.peek(), "Expect expression.");
-        Err(ParseError {})
+        Err(self.error(
+            &self.peek().clone(),
+            ErrorCode::ExpectExpression,
+            "Expect expression.",
+        ))
+    }
+
+    /// The scanner splits `"a ${b} c"` into `"a ${`, the tokens of `b`, and `} c"`.
+    fn interpolation(&mut self) -> ExprResult {
+        let mut parts = vec![LiteralExprExpr(LiteralExpr::new(self.previous().literal))];
+        loop {
+            parts.push(self.expression()?);
+            if !self.match_string_rest(StringInterpolation) {
+                break;
+            }
+            parts.push(LiteralExprExpr(LiteralExpr::new(self.previous().literal)));
+        }
+        if !self.match_string_rest(StringToken) {
+            return Err(self.error(
+                &self.peek().clone(),
+                ErrorCode::ExpectToken,
+                "Expect '}' after interpolated expression.",
+            ));
+        }
+        parts.push(LiteralExprExpr(LiteralExpr::new(self.previous().literal)));
+        Ok(InterpolationExpr(Interpolation::new(parts)))
+    }
+
+    /// Matches the rest of an interpolated string, which starts at the `}`
+    /// closing the embedded expression.
+    fn match_string_rest(&mut self, token_type: TokenType) -> bool {
+        self.continues_string() && self.match_next_token_type(vec![token_type])
+    }
+
+    fn continues_string(&self) -> bool {
+        matches!(self.peek().token_type, StringToken | StringInterpolation)
+            && self.peek().lexeme.starts_with('}')
     }
 
     fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token, ParseError> {
         if self.check_type(token_type) {
             Ok(self.advance())
         } else {
-            Err(self.error(&self.peek().clone(), message))
+            let code = if token_type == Identifier {
+                ErrorCode::ExpectName
+            } else {
+                ErrorCode::ExpectToken
+            };
+            Err(self.error(&self.peek().clone(), code, message))
         }
     }
 
-    fn error(&mut self, token: &Token, message: &str) -> ParseError {
-        error_with_token(token, message);
+    fn error(&mut self, token: &Token, code: ErrorCode, message: &str) -> ParseError {
+        self.diagnostics.push(Diagnostic::error_at_token(
+            Phase::Parse,
+            code,
+            token,
+            message,
+        ));
         ParseError {}
     }
 
@@ -471,3 +551,26 @@ impl<'a> Parser<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{ErrorCode, Lox, Phase};
+
+    #[test]
+    fn check_goes_on_after_a_bad_class_header() {
+        let source = "class A < {\n  m() {}\n}\nvar = 1;\n{ var b = b; }";
+        let diagnostics = Lox::new().check("test.lox", source);
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.phase, diagnostic.code, diagnostic.line))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Phase::Parse, ErrorCode::ExpectName, 1),
+                (Phase::Parse, ErrorCode::ExpectName, 4),
+                (Phase::Resolve, ErrorCode::ReadInOwnInitializer, 5),
+            ]
+        );
+    }
+}
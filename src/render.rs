@@ -0,0 +1,285 @@
+use std::fmt::Write;
+
+use crate::{
+    diagnostic::{Diagnostic, Frame, Location, Phase, Severity},
+    lox::LoxError,
+    span::{SourceMap, Span},
+};
+
+/// How [`crate::Lox::render`] formats an error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiagnosticFormat {
+    /// Exactly what jlox prints, which the Crafting Interpreters test suite expects.
+    #[default]
+    Short,
+    /// The offending source lines, with the spans underlined.
+    Pretty,
+    /// One JSON object per line for each diagnostic, for tools to parse.
+    Json,
+}
+
+pub(crate) fn render(error: &LoxError, format: DiagnosticFormat, sources: &SourceMap) -> String {
+    match (format, error) {
+        (DiagnosticFormat::Short, _) => error.to_string(),
+        (DiagnosticFormat::Pretty, LoxError::Conversion(error)) => format!("error: {}", error),
+        (DiagnosticFormat::Json, LoxError::Conversion(error)) => format!(
+            r#"{{"severity":"error","phase":null,"code":null,"message":{},"help":null,"file":null,"line":null,"column":null,"span":null,"labels":[],"trace":[],"omitted_frames":0}}"#,
+            json_string(&error.to_string())
+        ),
+        (DiagnosticFormat::Json, _) => error
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| json(diagnostic, sources))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        (DiagnosticFormat::Pretty, _) => error
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| pretty(diagnostic, sources))
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+    }
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    }
+}
+
+fn pretty(diagnostic: &Diagnostic, sources: &SourceMap) -> String {
+    let mut out = format!(
+        "{}[{}]: {}",
+        severity_name(diagnostic.severity),
+        diagnostic.code,
+        diagnostic.message
+    );
+    quote(&mut out, diagnostic, sources);
+    if let Some(help) = &diagnostic.help {
+        write!(out, "\n  = help: {}", help).unwrap();
+    }
+    if !diagnostic.trace.is_empty() {
+        write!(out, "\n  = traceback, innermost call first:").unwrap();
+    }
+    let (innermost, omitted, outermost) = shown_frames(&diagnostic.trace);
+    for (i, frame) in innermost.iter().chain(outermost).enumerate() {
+        if omitted > 0 && i == innermost.len() {
+            write!(out, "\n      ... {} more calls", omitted).unwrap();
+        }
+        match frame.call_site {
+            Some(span) => write!(
+                out,
+                "\n      {}() called at {}",
+                frame.function,
+                location(span, sources)
+            ),
+            None => write!(out, "\n      {}() called by the host", frame.function),
+        }
+        .unwrap();
+    }
+    out
+}
+
+/// The source lines the diagnostic points at, with its span and labels underlined.
+fn quote(out: &mut String, diagnostic: &Diagnostic, sources: &SourceMap) {
+    let Some((span, text)) = diagnostic
+        .span
+        .and_then(|span| Some((span, sources.text(span.file)?)))
+    else {
+        match diagnostic.location {
+            Location::Host => write!(out, "\n  --> called by the host"),
+            _ => write!(out, "\n  --> line {}", diagnostic.line),
+        }
+        .unwrap();
+        return;
+    };
+    write!(out, "\n  --> {}", location(span, sources)).unwrap();
+
+    let mut marks = vec![(span, '^', "")];
+    for label in &diagnostic.labels {
+        if label.span.file == span.file && label.span.line > 0 {
+            marks.push((label.span, '-', label.message.as_str()));
+        }
+    }
+    marks.sort_by_key(|(span, _, _)| span.line);
+    let width = marks
+        .iter()
+        .map(|(span, _, _)| span.line)
+        .max()
+        .unwrap_or(0)
+        .to_string()
+        .len();
+
+    write!(out, "\n{:width$} |", "").unwrap();
+    let mut previous_line = None;
+    for (span, marker, message) in marks {
+        let line = text.lines().nth(span.line as usize - 1).unwrap_or_default();
+        if previous_line != Some(span.line) {
+            let quoted = format!("{:>width$} | {}", span.line, line);
+            write!(out, "\n{}", quoted.trim_end()).unwrap();
+            previous_line = Some(span.line);
+        }
+        let marked = format!(
+            "{:width$} | {}{} {}",
+            "",
+            indent(line, span.column),
+            underline(text, span, marker),
+            message
+        );
+        write!(out, "\n{}", marked.trim_end()).unwrap();
+    }
+}
+
+/// The innermost and outermost frames of `trace`, and how many frames between
+/// them are left out. A runaway recursion would otherwise print hundreds of
+/// identical frames.
+fn shown_frames(trace: &[Frame]) -> (&[Frame], usize, &[Frame]) {
+    const SHOWN: usize = 10;
+    if trace.len() <= 2 * SHOWN {
+        return (trace, 0, &[]);
+    }
+    let omitted = trace.len() - 2 * SHOWN;
+    (&trace[..SHOWN], omitted, &trace[SHOWN + omitted..])
+}
+
+fn location(span: Span, sources: &SourceMap) -> String {
+    match sources.name(span.file) {
+        Some(name) => format!("{}:{}:{}", name, span.line, span.column),
+        None => format!("line {}", span.line),
+    }
+}
+
+/// Whitespace reaching `column`, keeping the line's tabs so the underline lines up.
+fn indent(line: &str, column: u32) -> String {
+    line.chars()
+        .take(column as usize - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect()
+}
+
+/// One marker per character of the span's first line, and at least one.
+fn underline(text: &str, span: Span, marker: char) -> String {
+    let spanned = text
+        .get(span.start as usize..span.end as usize)
+        .unwrap_or_default();
+    let length = spanned.chars().take_while(|&c| c != '\n').count().max(1);
+    marker.to_string().repeat(length)
+}
+
+fn json(diagnostic: &Diagnostic, sources: &SourceMap) -> String {
+    let phase = match diagnostic.phase {
+        Phase::Scan => "scan",
+        Phase::Parse => "parse",
+        Phase::Resolve => "resolve",
+        Phase::Runtime => "runtime",
+    };
+    let file = match diagnostic.span.and_then(|span| sources.name(span.file)) {
+        Some(name) => json_string(name),
+        None => "null".to_string(),
+    };
+    // jlox reports some errors, such as unterminated strings, on the line they
+    // end on; the span's line goes with its column.
+    let (line, column) = match (diagnostic.span, &diagnostic.location) {
+        (Some(span), _) => (span.line.to_string(), span.column.to_string()),
+        (None, Location::Host) => ("null".to_string(), "null".to_string()),
+        (None, _) => (diagnostic.line.to_string(), "null".to_string()),
+    };
+    let span = match diagnostic.span {
+        Some(span) => json_span(span),
+        None => "null".to_string(),
+    };
+    let labels: Vec<_> = diagnostic
+        .labels
+        .iter()
+        .map(|label| {
+            format!(
+                r#"{{"message":{},"line":{},"column":{},"span":{}}}"#,
+                json_string(&label.message),
+                label.span.line,
+                label.span.column,
+                json_span(label.span)
+            )
+        })
+        .collect();
+    let (innermost, omitted, outermost) = shown_frames(&diagnostic.trace);
+    let trace: Vec<_> = innermost
+        .iter()
+        .chain(outermost)
+        .map(|frame| match frame.call_site {
+            Some(span) => format!(
+                r#"{{"function":{},"file":{},"line":{},"column":{},"span":{}}}"#,
+                json_string(&frame.function),
+                sources
+                    .name(span.file)
+                    .map_or("null".to_string(), json_string),
+                span.line,
+                span.column,
+                json_span(span)
+            ),
+            None => format!(
+                r#"{{"function":{},"file":null,"line":null,"column":null,"span":null}}"#,
+                json_string(&frame.function)
+            ),
+        })
+        .collect();
+    format!(
+        r#"{{"severity":"{}","phase":"{}","code":"{}","message":{},"help":{},"file":{},"line":{},"column":{},"span":{},"labels":[{}],"trace":[{}],"omitted_frames":{}}}"#,
+        severity_name(diagnostic.severity),
+        phase,
+        diagnostic.code,
+        json_string(&diagnostic.message),
+        diagnostic
+            .help
+            .as_deref()
+            .map_or("null".to_string(), json_string),
+        file,
+        line,
+        column,
+        span,
+        labels.join(","),
+        trace.join(","),
+        omitted
+    )
+}
+
+fn json_span(span: Span) -> String {
+    format!(r#"{{"start":{},"end":{}}}"#, span.start, span.end)
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Limits, Lox};
+
+    #[test]
+    fn json_trace_keeps_the_innermost_and_outermost_frames() {
+        let mut lox = Lox::with_output(std::io::sink());
+        lox.set_limits(Limits {
+            max_call_depth: 50,
+            ..Limits::default()
+        });
+        let error = lox.eval("fun f() { f(); } f();").unwrap_err();
+        assert_eq!(error.diagnostics()[0].trace.len(), 50);
+        let json = lox.render(&error, DiagnosticFormat::Json);
+        assert_eq!(json.matches(r#""function":"f""#).count(), 20);
+        assert!(json.ends_with(r#""omitted_frames":30}"#));
+    }
+}
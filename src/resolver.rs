@@ -2,12 +2,15 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
+use crate::diagnostic::{Diagnostic, Diagnostics, Phase};
 use crate::environment_tree::EnvironmentTree;
-use crate::error_with_token;
+use crate::error_code::ErrorCode;
 use crate::expr::Expr::VariableExpr;
 use crate::expr::{
-    self, Assign, Binary, Call, Expr, Get, Grouping, LiteralExpr, Logical, Set, Unary, Variable,
+    self, Assign, Binary, Call, Expr, Get, Grouping, Interpolation, LiteralExpr, Logical, Set,
+    Unary, Variable,
 };
+use crate::span::Span;
 use crate::stmt::{self, Block, Class, Expression, Function, If, Print, Return, Stmt, Var, While};
 use crate::token::{Literal, Token};
 #[derive(Copy, Clone)]
@@ -23,23 +26,49 @@ enum ClassType {
     Class,
     Subclass,
 }
-pub(crate) struct Resolver {
-    scopes: Vec<HashMap<String, bool>>,
+#[derive(Copy, Clone)]
+struct Local {
+    defined: bool,
+    declared_at: Span,
+}
+impl Local {
+    /// `this` and `super`, which no statement declares.
+    fn implicit() -> Self {
+        Self {
+            defined: true,
+            declared_at: Span::default(),
+        }
+    }
+}
+
+pub(crate) struct Resolver<'a> {
+    scopes: Vec<HashMap<String, Local>>,
     env: Rc<RefCell<EnvironmentTree>>,
     current_function: FunctionType,
     current_class: ClassType,
+    diagnostics: &'a mut Diagnostics,
 }
 
-impl Resolver {
-    pub(crate) fn new(env: Rc<RefCell<EnvironmentTree>>) -> Self {
+impl<'a> Resolver<'a> {
+    pub(crate) fn new(env: Rc<RefCell<EnvironmentTree>>, diagnostics: &'a mut Diagnostics) -> Self {
         Self {
             scopes: Vec::new(),
             env,
             current_function: FunctionType::NotFun,
             current_class: ClassType::NotClass,
+            diagnostics,
         }
     }
 
+    fn error(&mut self, token: &Token, code: ErrorCode, message: &str) {
+        self.diagnostics.push(Diagnostic::error_at_token(
+            Phase::Resolve,
+            code,
+            token,
+            message,
+        ));
+    }
+
     pub(crate) fn resolve(&mut self, statements: &[Stmt]) {
         for statement in statements {
             self.resolve_statement(statement);
@@ -69,20 +98,32 @@ impl Resolver {
     fn declare(&mut self, name: &Token) {
         if !self.scopes.is_empty() {
             let map = self.scopes.last_mut().unwrap();
-            if map.contains_key(&name.lexeme) {
-                error_with_token(name, "Already a variable with this name in this scope.");
+            if let Some(previous) = map.get(&name.lexeme) {
+                let diagnostic = Diagnostic::error_at_token(
+                    Phase::Resolve,
+                    ErrorCode::AlreadyDeclared,
+                    name,
+                    "Already a variable with this name in this scope.",
+                )
+                .with_label(previous.declared_at, "previous declaration here");
+                self.diagnostics.push(diagnostic);
             } else {
-                map.insert(name.lexeme.to_string(), false);
+                let local = Local {
+                    defined: false,
+                    declared_at: name.span,
+                };
+                map.insert(name.lexeme.to_string(), local);
             }
         }
     }
 
     fn define(&mut self, name: &Token) {
-        if !self.scopes.is_empty() {
-            self.scopes
-                .last_mut()
-                .unwrap()
-                .insert(name.lexeme.to_string(), true);
+        if let Some(local) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.lexeme))
+        {
+            local.defined = true;
         }
     }
 
@@ -109,7 +150,7 @@ impl Resolver {
     }
 }
 
-impl expr::Visitor<()> for Resolver {
+impl expr::Visitor<()> for Resolver<'_> {
     fn visit_binary_expr(&mut self, expr: &Binary) {
         self.resolve_expr(&expr.left);
         self.resolve_expr(&expr.right);
@@ -117,18 +158,17 @@ impl expr::Visitor<()> for Resolver {
     fn visit_grouping_expr(&mut self, expr: &Grouping) {
         self.resolve_expr(&expr.expression);
     }
-    fn visit_literalexpr_expr(&mut self, _expr: &LiteralExpr) {
-        ()
-    }
+    fn visit_literalexpr_expr(&mut self, _expr: &LiteralExpr) {}
     fn visit_unary_expr(&mut self, expr: &Unary) {
         self.resolve_expr(&expr.right);
     }
     fn visit_variable_expr(&mut self, expr: &Variable) {
         if !self.scopes.is_empty() {
             if let Some(value) = self.scopes.last().unwrap().get(&expr.name.lexeme) {
-                if *value == false {
-                    crate::error_with_token(
+                if !value.defined {
+                    self.error(
                         &expr.name,
+                        ErrorCode::ReadInOwnInitializer,
                         "Can't read local variable in its own initializer.",
                     );
                 }
@@ -156,6 +196,11 @@ impl expr::Visitor<()> for Resolver {
             self.resolve_expr(argument);
         }
     }
+    fn visit_interpolation_expr(&mut self, expr: &Interpolation) {
+        for part in &expr.parts {
+            self.resolve_expr(part);
+        }
+    }
     fn visit_get_expr(&mut self, expr: &Get) {
         self.resolve_expr(&expr.object);
     }
@@ -163,9 +208,13 @@ impl expr::Visitor<()> for Resolver {
         self.resolve_expr(&expr.value);
         self.resolve_expr(&expr.object);
     }
-    fn visit_this_expr(&mut self, expr: &expr::This) -> () {
+    fn visit_this_expr(&mut self, expr: &expr::This) {
         if let ClassType::NotClass = self.current_class {
-            error_with_token(&expr.keyword, "Can't use 'this' outside of a class.");
+            self.error(
+                &expr.keyword,
+                ErrorCode::ThisOutsideClass,
+                "Can't use 'this' outside of a class.",
+            );
             return;
         }
         let index = self.resolve_local(&expr.keyword);
@@ -173,13 +222,16 @@ impl expr::Visitor<()> for Resolver {
             self.insert_step_for_id(expr.id, step);
         }
     }
-    fn visit_super_expr(&mut self, expr: &expr::Super) -> () {
+    fn visit_super_expr(&mut self, expr: &expr::Super) {
         match self.current_class {
-            ClassType::NotClass => {
-                error_with_token(&expr.keyword, "Can't use 'super' outside of a class.")
-            }
-            ClassType::Class => error_with_token(
+            ClassType::NotClass => self.error(
+                &expr.keyword,
+                ErrorCode::SuperOutsideClass,
+                "Can't use 'super' outside of a class.",
+            ),
+            ClassType::Class => self.error(
                 &expr.keyword,
+                ErrorCode::SuperWithoutSuperclass,
                 "Can't use 'super' in a class with no superclass.",
             ),
             ClassType::Subclass => (),
@@ -191,7 +243,7 @@ impl expr::Visitor<()> for Resolver {
     }
 }
 
-impl stmt::Visitor<()> for Resolver {
+impl stmt::Visitor<()> for Resolver<'_> {
     fn visit_expression_stmt(&mut self, stmt: &Expression) {
         self.resolve_expr(&stmt.expression);
     }
@@ -224,17 +276,21 @@ impl stmt::Visitor<()> for Resolver {
         self.define(&stmt.name);
         self.resolve_function(&stmt, FunctionType::Fun);
     }
+    fn visit_error_stmt(&mut self, _stmt: &stmt::Error) {}
     fn visit_return_stmt(&mut self, stmt: &Return) {
         match self.current_function {
-            FunctionType::NotFun => {
-                error_with_token(&stmt.keyword, "Can't return from top-level code.")
-            }
+            FunctionType::NotFun => self.error(
+                &stmt.keyword,
+                ErrorCode::ReturnFromTopLevel,
+                "Can't return from top-level code.",
+            ),
             FunctionType::Initializer => {
                 if let Expr::LiteralExprExpr(lee) = &stmt.value {
                     if let Literal::NoneLiteral = lee.value {
                     } else {
-                        error_with_token(
+                        self.error(
                             &stmt.keyword,
+                            ErrorCode::ReturnValueFromInitializer,
                             "Can't return a value from an initializer.",
                         );
                     }
@@ -253,22 +309,26 @@ impl stmt::Visitor<()> for Resolver {
         if let Some(superclass) = stmt.superclass.clone() {
             self.current_class = ClassType::Subclass;
             if stmt.name.lexeme == superclass.name.lexeme {
-                error_with_token(&superclass.name, "A class can't inherit from itself.");
+                self.error(
+                    &superclass.name,
+                    ErrorCode::InheritFromSelf,
+                    "A class can't inherit from itself.",
+                );
             }
             self.resolve_expr(&VariableExpr(superclass));
         }
-        if let Some(_) = stmt.superclass {
+        if stmt.superclass.is_some() {
             self.begin_scope();
             self.scopes
                 .last_mut()
                 .unwrap()
-                .insert("super".to_string(), true);
+                .insert("super".to_string(), Local::implicit());
         }
         self.begin_scope();
         self.scopes
             .last_mut()
             .unwrap()
-            .insert("this".to_string(), true);
+            .insert("this".to_string(), Local::implicit());
         for method in &stmt.methods {
             if method.name.lexeme == "init" {
                 self.resolve_function(method, FunctionType::Initializer);
@@ -277,7 +337,7 @@ impl stmt::Visitor<()> for Resolver {
             }
         }
         self.end_scope();
-        if let Some(_) = stmt.superclass {
+        if stmt.superclass.is_some() {
             self.end_scope();
         }
 
@@ -1,20 +1,35 @@
+use crate::diagnostic::{Diagnostic, Diagnostics, Location, Phase};
+use crate::error_code::ErrorCode;
+use crate::span::{FileId, Span};
 use crate::token::Literal;
 use crate::token::Literal::*;
 use crate::token::Token;
 use crate::token_type::TokenType;
 use crate::token_type::TokenType::*;
+use crate::trivia::{Trivia, TriviaKind};
 use std::collections::HashMap;
-pub(crate) struct Scanner {
+pub(crate) struct Scanner<'a> {
     pub(crate) tokens: Vec<Token>,
-    source_chars: Vec<char>,
+    /// Whether to keep whitespace and comments in `trivia`, for tools that
+    /// need every byte of the source.
+    pub(crate) keep_trivia: bool,
+    pub(crate) trivia: Vec<Trivia>,
+    source: &'a str,
+    file: FileId,
     start: usize,
     current: usize,
     line: usize,
+    /// How many characters of the current line come before `current`.
+    column: usize,
+    start_line: usize,
+    start_column: usize,
+    /// How many `{` are open inside each `${` we are in, innermost last.
+    interpolations: Vec<usize>,
     keywords: HashMap<String, TokenType>,
+    diagnostics: &'a mut Diagnostics,
 }
-impl Scanner {
-    pub(crate) fn new(source: &str) -> Self {
-        let source_chars = source.chars().collect();
+impl<'a> Scanner<'a> {
+    pub(crate) fn new(source: &'a str, file: FileId, diagnostics: &'a mut Diagnostics) -> Self {
         let mut keywords = HashMap::new();
         keywords.insert(String::from("and"), And);
         keywords.insert(String::from("class"), Class);
@@ -35,27 +50,64 @@ impl Scanner {
 
         Self {
             tokens: Vec::<Token>::new(),
-            source_chars,
+            keep_trivia: false,
+            trivia: Vec::new(),
+            source,
+            file,
             start: 0,
             current: 0,
             line: 1,
+            column: 0,
+            start_line: 1,
+            start_column: 1,
+            interpolations: Vec::new(),
             keywords,
+            diagnostics,
         }
     }
 
     pub(crate) fn scan_tokens(&mut self) {
+        // Editors on Windows may start UTF-8 files with a byte order mark.
+        if self.source.starts_with('\u{feff}') {
+            self.mark_start();
+            self.current = '\u{feff}'.len_utf8();
+            self.add_trivia(TriviaKind::Whitespace);
+        }
         while !self.is_at_end() {
             // We are at the beginning of the next lexeme.
-            self.start = self.current;
+            self.mark_start();
             self.scan_token();
         }
 
+        self.mark_start();
+        let span = self.span();
         self.tokens
-            .push(Token::from(Eof, String::from(""), NoneLiteral, self.line));
+            .push(Token::from(Eof, String::from(""), NoneLiteral, span));
+    }
+
+    fn mark_start(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column + 1;
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            file: self.file,
+            start: self.start as u32,
+            end: self.current as u32,
+            line: self.start_line as u32,
+            column: self.start_column as u32,
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.column = 0;
     }
 
     fn is_at_end(&self) -> bool {
-        self.current >= self.source_chars.len()
+        self.current >= self.source.len()
     }
 
     fn scan_token(&mut self) {
@@ -63,8 +115,24 @@ impl Scanner {
         match c {
             '(' => self.add_token_null_literal(LeftParen),
             ')' => self.add_token_null_literal(RightParen),
-            '{' => self.add_token_null_literal(LeftBrace),
-            '}' => self.add_token_null_literal(RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token_null_literal(LeftBrace);
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    // Closes a `${`, so the string goes on.
+                    self.interpolations.pop();
+                    self.handle_string();
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.add_token_null_literal(RightBrace);
+                }
+                None => self.add_token_null_literal(RightBrace),
+            },
             ',' => self.add_token_null_literal(Comma),
             '.' => self.add_token_null_literal(Dot),
             '-' => self.add_token_null_literal(Minus),
@@ -98,17 +166,29 @@ impl Scanner {
             '/' => {
                 if self.match_cur('/') {
                     // A comment goes until the end of the line.
-                    while self.peek() != '\n' && !self.is_at_end() {
+                    while self.peek() != '\n'
+                        && !(self.peek() == '\r' && self.peek_next() == '\n')
+                        && !self.is_at_end()
+                    {
                         self.advance();
                     }
+                    self.add_trivia(TriviaKind::LineComment);
+                } else if self.match_cur('*') {
+                    self.handle_block_comment();
+                    self.add_trivia(TriviaKind::BlockComment);
                 } else {
                     self.add_token_null_literal(Slash)
                 }
             }
-            ' ' => (),
-            '\r' => (),
-            '\t' => (),
-            '\n' => self.line += 1,
+            '\r' if self.match_cur('\n') => {
+                self.add_trivia(TriviaKind::Newline);
+                self.new_line();
+            }
+            ' ' | '\r' | '\t' => self.add_trivia(TriviaKind::Whitespace),
+            '\n' => {
+                self.add_trivia(TriviaKind::Newline);
+                self.new_line();
+            }
             '"' => self.handle_string(),
             r => {
                 if is_numeric(r) {
@@ -116,7 +196,10 @@ impl Scanner {
                 } else if is_alpha(r) {
                     self.handle_identifier();
                 } else {
-                    crate::error(self.line, &format!("Unexpected character: {r}."));
+                    self.error(
+                        ErrorCode::UnexpectedCharacter,
+                        &format!("Unexpected character: {r}."),
+                    );
                 }
             }
         }
@@ -127,85 +210,281 @@ impl Scanner {
             self.advance();
         }
 
-        let token_slice = &self.source_chars[self.start..self.current];
-        let text: String = token_slice.into_iter().collect();
-        match self.keywords.get(&text) {
+        let text = &self.source[self.start..self.current];
+        match self.keywords.get(text) {
             None => self.add_token_null_literal(Identifier),
             Some(id) => self.add_token_null_literal(*id),
         }
     }
 
     fn handle_number(&mut self) {
-        while self.peek().is_ascii_digit() {
-            self.advance();
+        let radix = match (&self.source[self.start..self.current], self.peek()) {
+            ("0", 'x' | 'X') => Some((16, "hex")),
+            ("0", 'b' | 'B') => Some((2, "binary")),
+            ("0", 'o' | 'O') => Some((8, "octal")),
+            _ => None,
+        };
+        let value = match radix {
+            Some((radix, name)) => self.radix_number(radix, name),
+            None => self.decimal_number(),
+        };
+        let value = value.unwrap_or_else(|message| {
+            // Take in the rest of the literal, so the error covers all of it.
+            while is_alpha_numeric(self.peek()) {
+                self.advance();
+            }
+            self.error(ErrorCode::MalformedNumber, &message);
+            0.0
+        });
+        self.add_token(Number, Float(value));
+    }
+
+    /// `0x`, `0b` or `0o` followed by digits in that base.
+    fn radix_number(&mut self, radix: u32, name: &str) -> Result<f64, String> {
+        let prefix = format!("0{}", self.advance());
+        let digits = self.digits(radix, &prefix)?;
+        let c = self.peek();
+        if is_alpha_numeric(c) {
+            return Err(format!("Invalid digit '{}' in {} literal.", c, name));
         }
+        Ok(digits.chars().fold(0.0, |value, digit| {
+            value * radix as f64 + digit.to_digit(radix).unwrap() as f64
+        }))
+    }
+
+    fn decimal_number(&mut self) -> Result<f64, String> {
+        self.current = self.start;
+        self.column = self.start_column - 1;
+        let mut text = self.digits(10, "")?;
 
         // Look for a fractional part.
         if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             // Consume the "."
             self.advance();
+            text.push('.');
+            text += &self.digits(10, ".")?;
+        }
 
-            while self.peek().is_ascii_digit() {
-                self.advance();
+        // `1else` is a number and a name, not a missing exponent.
+        if matches!(self.peek(), 'e' | 'E') && !is_alpha(self.peek_next()) {
+            let e = self.advance();
+            text.push('e');
+            if matches!(self.peek(), '+' | '-') {
+                text.push(self.advance());
             }
+            text += &self.digits(10, &e.to_string())?;
         }
 
-        let token_slice = &self.source_chars[self.start..self.current];
-        let number_text: String = token_slice.into_iter().collect();
+        Ok(text.parse().unwrap())
+    }
 
-        self.add_token(Number, Float(number_text.parse().unwrap()));
+    /// A run of digits in `radix`, any two of which may be separated by one `_`.
+    /// Returns them without the separators.
+    fn digits(&mut self, radix: u32, after: &str) -> Result<String, String> {
+        let mut digits = String::new();
+        loop {
+            let c = self.peek();
+            if c == '_' && !digits.is_empty() {
+                self.advance();
+                if !self.peek().is_digit(radix) {
+                    return Err("Expect a digit after '_'.".to_string());
+                }
+            } else if c.is_digit(radix) {
+                self.advance();
+                digits.push(c);
+            } else {
+                break;
+            }
+        }
+        if digits.is_empty() {
+            return Err(format!("Expect digits after '{}'.", after));
+        }
+        Ok(digits)
     }
 
     fn handle_string(&mut self) {
+        let mut value = String::new();
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            match self.advance() {
+                '\\' => value.extend(self.escape()),
+                '$' if self.match_cur('{') => {
+                    self.add_token(StringInterpolation, StringLiteral(value));
+                    self.interpolations.push(0);
+                    return;
+                }
+                '\n' => {
+                    self.new_line();
+                    value.push('\n');
+                }
+                c => value.push(c),
             }
-            self.advance();
         }
 
         if self.is_at_end() {
-            crate::error(self.line, "Unterminated string.");
+            self.error(ErrorCode::UnterminatedString, "Unterminated string.");
             return;
         }
 
         // The closing ".
         self.advance();
-
-        // Trim the surrounding quotes.
-        let token_slice = &self.source_chars[self.start + 1..self.current - 1];
-        let value: String = token_slice.into_iter().collect();
         self.add_token(StringToken, StringLiteral(value));
     }
 
+    /// Scans the escape sequence after a `\\` and returns the character it stands for.
+    fn escape(&mut self) -> Option<char> {
+        let start = self.current - 1;
+        if self.is_at_end() {
+            // Reported as an unterminated string.
+            return None;
+        }
+        let escaped = match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '$' => '$',
+            'u' => return self.unicode_escape(start),
+            '\n' => {
+                self.escape_error(start, "Invalid escape sequence at end of line.");
+                self.new_line();
+                return None;
+            }
+            c => {
+                self.escape_error(start, &format!("Invalid escape sequence '\\{}'.", c));
+                return None;
+            }
+        };
+        Some(escaped)
+    }
+
+    /// `\\u{XXXX}`, with one to six hex digits naming a Unicode scalar value.
+    fn unicode_escape(&mut self, start: usize) -> Option<char> {
+        if !self.match_cur('{') {
+            self.escape_error(start, "Expect '{' after '\\u'.");
+            return None;
+        }
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let digits = &self.source[digits_start..self.current];
+        if !self.match_cur('}') {
+            self.escape_error(start, "Expect '}' after Unicode escape.");
+            return None;
+        }
+        if digits.is_empty() || digits.len() > 6 {
+            self.escape_error(start, "Unicode escape must have 1 to 6 hex digits.");
+            return None;
+        }
+        let escaped = u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32);
+        if escaped.is_none() {
+            let message = format!("Invalid Unicode code point '{}'.", digits);
+            self.escape_error(start, &message);
+        }
+        escaped
+    }
+
+    /// Points at the escape sequence from `start`, rather than the whole string.
+    fn escape_error(&mut self, start: usize, message: &str) {
+        let span = Span {
+            file: self.file,
+            start: start as u32,
+            end: self.current as u32,
+            line: self.line as u32,
+            column: (self.column - self.source[start..self.current].chars().count()) as u32 + 1,
+        };
+        let diagnostic = Diagnostic::error(
+            Phase::Scan,
+            ErrorCode::InvalidEscape,
+            self.line,
+            Location::Line,
+            message,
+        );
+        self.diagnostics.push(diagnostic.with_span(span));
+    }
+
+    /// Block comments nest, so `/* a /* b */ c */` is one comment.
+    fn handle_block_comment(&mut self) {
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() {
+                self.error_at(
+                    self.start_line,
+                    ErrorCode::UnterminatedComment,
+                    "Unterminated block comment.",
+                );
+                return;
+            }
+            match self.advance() {
+                '\n' => self.new_line(),
+                '/' if self.match_cur('*') => depth += 1,
+                '*' if self.match_cur('/') => depth -= 1,
+                _ => (),
+            }
+        }
+    }
+
+    fn error(&mut self, code: ErrorCode, message: &str) {
+        self.error_at(self.line, code, message);
+    }
+
+    fn error_at(&mut self, line: usize, code: ErrorCode, message: &str) {
+        let span = self.span();
+        let diagnostic = Diagnostic::error(Phase::Scan, code, line, Location::Line, message);
+        self.diagnostics.push(diagnostic.with_span(span));
+    }
+
     fn match_cur(&mut self, expected: char) -> bool {
-        if self.is_at_end() || self.source_chars[self.current] != expected {
+        if self.peek() != expected {
             false
         } else {
-            self.current += 1;
+            self.current += expected.len_utf8();
+            self.column += 1;
             true
         }
     }
 
     fn peek(&mut self) -> char {
-        if self.is_at_end() {
-            '\0'
-        } else {
-            self.source_chars[self.current]
-        }
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&mut self) -> char {
-        if self.current + 1 >= self.source_chars.len() {
-            '\0'
-        } else {
-            self.source_chars[self.current + 1]
-        }
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     fn advance(&mut self) -> char {
-        self.current += 1;
-        self.source_chars[self.current - 1]
+        let c = self.peek();
+        self.current += c.len_utf8();
+        self.column += 1;
+        c
+    }
+
+    /// Keeps the text since the start of the lexeme, joining runs of whitespace.
+    fn add_trivia(&mut self, kind: TriviaKind) {
+        if !self.keep_trivia {
+            return;
+        }
+        let text = &self.source[self.start..self.current];
+        let span = self.span();
+        if let Some(last) = self.trivia.last_mut() {
+            if kind == TriviaKind::Whitespace
+                && last.kind == TriviaKind::Whitespace
+                && last.span.end == span.start
+            {
+                last.text.push_str(text);
+                last.span.end = span.end;
+                return;
+            }
+        }
+        self.trivia.push(Trivia {
+            kind,
+            text: text.to_string(),
+            span,
+        });
     }
 
     fn add_token_null_literal(&mut self, token_type: TokenType) {
@@ -213,11 +492,10 @@ impl Scanner {
     }
 
     fn add_token(&mut self, token_type: TokenType, literal: Literal) {
-        let token_slice = &self.source_chars[self.start..self.current];
-        let text: String = token_slice.into_iter().collect();
-
+        let text = self.source[self.start..self.current].to_string();
+        let span = self.span();
         self.tokens
-            .push(Token::from(token_type, text, literal, self.line));
+            .push(Token::from(token_type, text, literal, span));
     }
 }
 
@@ -225,10 +503,105 @@ fn is_numeric(c: char) -> bool {
     c.is_ascii_digit()
 }
 
+/// Whether `c` can start a name: `_` or a letter from any script, per Unicode's XID_Start.
 fn is_alpha(c: char) -> bool {
-    c.is_ascii_alphabetic() || c == '_'
+    c == '_' || unicode_ident::is_xid_start(c)
 }
 
+/// Whether `c` can continue a name, per XID_Continue, which includes digits and `_`.
 fn is_alpha_numeric(c: char) -> bool {
-    is_alpha(c) || is_numeric(c)
+    unicode_ident::is_xid_continue(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(source: &str) -> (Vec<Token>, Vec<Diagnostic>) {
+        let mut diagnostics = Diagnostics::default();
+        let mut scanner = Scanner::new(source, FileId::default(), &mut diagnostics);
+        scanner.scan_tokens();
+        let tokens = scanner.tokens;
+        (tokens, diagnostics.into_vec())
+    }
+
+    fn types(tokens: &[Token]) -> Vec<TokenType> {
+        tokens.iter().map(|token| token.token_type).collect()
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<ErrorCode> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.code)
+            .collect()
+    }
+    #[test]
+    fn escape_beyond_unicode_is_invalid() {
+        let (tokens, diagnostics) = scan(r#""\u{110000}""#);
+        assert_eq!(types(&tokens), [StringToken, Eof]);
+        assert_eq!(codes(&diagnostics), [ErrorCode::InvalidEscape]);
+        let (tokens, _) = scan(r#""\u{1F600}\t""#);
+        assert!(matches!(&tokens[0].literal, StringLiteral(s) if s == "\u{1F600}\t"));
+    }
+
+    #[test]
+    fn interpolations_nest() {
+        let (tokens, diagnostics) = scan(r#""a${ "b ${1} c" }d""#);
+        assert_eq!(
+            types(&tokens),
+            [
+                StringInterpolation,
+                StringInterpolation,
+                Number,
+                StringToken,
+                StringToken,
+                Eof
+            ]
+        );
+        assert!(diagnostics.is_empty());
+        let lexemes: Vec<_> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert_eq!(
+            lexemes,
+            [r#""a${"#, r#""b ${"#, "1", r#"} c""#, r#"}d""#, ""]
+        );
+    }
+
+    #[test]
+    fn radix_prefix_needs_digits() {
+        let (tokens, diagnostics) = scan("0x");
+        assert_eq!(types(&tokens), [Number, Eof]);
+        assert_eq!(codes(&diagnostics), [ErrorCode::MalformedNumber]);
+        assert!(matches!(scan("0xff").0[0].literal, Float(n) if n == 255.0));
+    }
+    #[test]
+    fn separators_sit_between_digits() {
+        let (tokens, diagnostics) = scan("1__0");
+        assert_eq!(types(&tokens), [Number, Eof]);
+        assert_eq!(codes(&diagnostics), [ErrorCode::MalformedNumber]);
+        assert!(matches!(scan("1_000").0[0].literal, Float(n) if n == 1000.0));
+    }
+    #[test]
+    fn number_ends_before_a_keyword() {
+        let (tokens, diagnostics) = scan("1else");
+        assert_eq!(types(&tokens), [Number, Else, Eof]);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn identifiers_are_unicode() {
+        let (tokens, diagnostics) = scan("\u{feff}var café_1 = 1;");
+        assert_eq!(
+            types(&tokens),
+            [Var, Identifier, Equal, Number, Semicolon, Eof]
+        );
+        assert_eq!(tokens[1].lexeme, "café_1");
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let (tokens, _) = scan("\"é\" x\n  y");
+        assert_eq!((tokens[1].span.line, tokens[1].span.column), (1, 5));
+        assert_eq!((tokens[2].span.line, tokens[2].span.column), (2, 3));
+    }
 }
@@ -0,0 +1,121 @@
+use std::collections::{HashMap, VecDeque};
+
+/// Identifies one source passed to [`crate::Lox::eval`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FileId(pub(crate) u32);
+
+/// Where a token sits in its source.
+///
+/// The fields are `u32` to keep tokens, and the runtime errors that carry
+/// them, small.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub file: FileId,
+    /// Byte offset of the first character.
+    pub start: u32,
+    /// Byte offset just past the last character.
+    pub end: u32,
+    /// The line the token starts on, counting from 1.
+    pub line: u32,
+    /// The column the token starts at, counting characters from 1.
+    pub column: u32,
+}
+
+impl Span {
+    /// A span that only knows its line, for tokens that aren't in any source.
+    pub(crate) fn at_line(line: usize) -> Self {
+        Self {
+            line: line as u32,
+            ..Self::default()
+        }
+    }
+}
+
+/// How many of the sources an engine was given it keeps.
+const KEPT_SOURCES: usize = 100;
+
+/// The latest sources an engine has run, so diagnostics can quote them.
+#[derive(Default)]
+pub(crate) struct SourceMap {
+    files: VecDeque<(String, String)>,
+    /// The id of the oldest source still kept.
+    first: u32,
+    /// The slot of each source that was only checked, by name.
+    checked: HashMap<String, FileId>,
+}
+
+impl SourceMap {
+    /// Keeps `text`, and forgets the oldest source if there are too many.
+    pub(crate) fn add(&mut self, name: &str, text: &str) -> FileId {
+        self.files.push_back((name.to_string(), text.to_string()));
+        if self.files.len() > KEPT_SOURCES {
+            self.files.pop_front();
+            self.first += 1;
+        }
+        FileId(self.first + self.files.len() as u32 - 1)
+    }
+
+    /// Like [`SourceMap::add`], but overwrites the source an earlier call gave
+    /// the same name, so checking a file over and over doesn't keep every version.
+    pub(crate) fn replace(&mut self, name: &str, text: &str) -> FileId {
+        let kept = self.checked.get(name).copied();
+        match kept.and_then(|file| self.get_mut(file)) {
+            Some(slot) => slot.1 = text.to_string(),
+            None => {
+                let file = self.add(name, text);
+                self.checked.insert(name.to_string(), file);
+            }
+        }
+        self.checked[name]
+    }
+
+    pub(crate) fn name(&self, file: FileId) -> Option<&str> {
+        self.get(file).map(|(name, _)| name.as_str())
+    }
+
+    pub(crate) fn text(&self, file: FileId) -> Option<&str> {
+        self.get(file).map(|(_, text)| text.as_str())
+    }
+
+    fn get(&self, file: FileId) -> Option<&(String, String)> {
+        self.files.get(file.0.checked_sub(self.first)? as usize)
+    }
+
+    fn get_mut(&mut self, file: FileId) -> Option<&mut (String, String)> {
+        self.files.get_mut(file.0.checked_sub(self.first)? as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_reuses_the_slot_of_the_same_name() {
+        let mut sources = SourceMap::default();
+        let run = sources.add("a.lox", "print 1;");
+        let first = sources.replace("a.lox", "print 2;");
+        let second = sources.replace("a.lox", "print 3;");
+        assert_ne!(run, first);
+        assert_eq!(first, second);
+        assert_eq!(sources.text(run), Some("print 1;"));
+        assert_eq!(sources.text(second), Some("print 3;"));
+        assert_ne!(sources.replace("b.lox", ""), second);
+    }
+
+    #[test]
+    fn old_sources_are_forgotten() {
+        let mut sources = SourceMap::default();
+        let checked = sources.replace("a.lox", "print 1;");
+        let files: Vec<_> = (0..KEPT_SOURCES)
+            .map(|i| sources.add("<eval>", &i.to_string()))
+            .collect();
+        assert_eq!(sources.text(checked), None);
+        assert_eq!(sources.text(files[0]), Some("0"));
+        assert_eq!(sources.text(files[KEPT_SOURCES - 1]), Some("99"));
+        let checked_again = sources.replace("a.lox", "print 2;");
+        assert_ne!(checked_again, checked);
+        assert_eq!(sources.text(files[0]), None);
+        assert_eq!(sources.text(checked_again), Some("print 2;"));
+    }
+}
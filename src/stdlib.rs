@@ -0,0 +1,152 @@
+use std::{
+    cell::Cell,
+    env, fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    conversion::{FromLox, IntoLox},
+    environment_tree::EnvironmentTree,
+    lox_callable::{Arity, LoxCallable, NativeFunction, NativeResult},
+    token::Literal,
+};
+
+/// The groups of built-in native functions an engine installs into its globals.
+///
+/// A script can only reach the host through the groups that are enabled;
+/// calling a function from a disabled group fails with "Undefined variable".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// `clock()`
+    pub time: bool,
+    /// `readFile(path)` and `writeFile(path, contents)`
+    pub file_io: bool,
+    /// `getEnv(name)`
+    pub env: bool,
+    /// `exit(code)`
+    pub exit: bool,
+    /// `random()`
+    pub random: bool,
+}
+
+impl Capabilities {
+    pub fn none() -> Self {
+        Self {
+            time: false,
+            file_io: false,
+            env: false,
+            exit: false,
+            random: false,
+        }
+    }
+
+    pub fn all() -> Self {
+        Self {
+            time: true,
+            file_io: true,
+            env: true,
+            exit: true,
+            random: true,
+        }
+    }
+}
+
+/// Only `clock`, like jlox.
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            time: true,
+            ..Self::none()
+        }
+    }
+}
+
+pub(crate) fn install(env: &mut EnvironmentTree, capabilities: Capabilities) {
+    if capabilities.time {
+        define(env, "clock", Arity::Fixed(0), clock);
+    }
+    if capabilities.file_io {
+        define(env, "readFile", Arity::Fixed(1), read_file);
+        define(env, "writeFile", Arity::Fixed(2), write_file);
+    }
+    if capabilities.env {
+        define(env, "getEnv", Arity::Fixed(1), get_env);
+    }
+    if capabilities.exit {
+        define(env, "exit", Arity::Fixed(1), exit);
+    }
+    if capabilities.random {
+        let state = Cell::new(random_seed());
+        define(env, "random", Arity::Fixed(0), move |_| {
+            Ok(Literal::Float(next_random(&state)))
+        });
+    }
+}
+
+fn define(
+    env: &mut EnvironmentTree,
+    name: &str,
+    arity: Arity,
+    function: impl Fn(&[Literal]) -> NativeResult + 'static,
+) {
+    let native = NativeFunction::new(name, arity, function);
+    env.define_global(
+        name.to_string(),
+        Literal::Callable(LoxCallable::Native(native)),
+    );
+}
+
+fn clock(_: &[Literal]) -> NativeResult {
+    Ok(Literal::Float(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64(),
+    ))
+}
+
+fn read_file(arguments: &[Literal]) -> NativeResult {
+    let path = String::from_lox(&arguments[0])?;
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(contents.into_lox()),
+        Err(error) => Err(format!("Could not read file '{}': {}.", path, error).into()),
+    }
+}
+
+fn write_file(arguments: &[Literal]) -> NativeResult {
+    let path = String::from_lox(&arguments[0])?;
+    let contents = String::from_lox(&arguments[1])?;
+    match fs::write(&path, contents) {
+        Ok(()) => Ok(Literal::NoneLiteral),
+        Err(error) => Err(format!("Could not write file '{}': {}.", path, error).into()),
+    }
+}
+
+fn get_env(arguments: &[Literal]) -> NativeResult {
+    let name = String::from_lox(&arguments[0])?;
+    Ok(env::var(name).ok().into_lox())
+}
+
+fn exit(arguments: &[Literal]) -> NativeResult {
+    let code = i32::from_lox(&arguments[0])?;
+    std::process::exit(code)
+}
+
+fn random_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64;
+    // xorshift gets stuck on a zero state.
+    nanos | 1
+}
+
+/// A xorshift64* step, scaled to [0, 1).
+fn next_random(state: &Cell<u64>) -> f64 {
+    let mut x = state.get();
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    state.set(x);
+    (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+}
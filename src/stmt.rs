@@ -15,6 +15,7 @@ pub(crate) enum Stmt {
     FunctionStmt(Rc<Function>),
     ReturnStmt(Box<Return>),
     ClassStmt(Rc<Class>),
+    ErrorStmt(Box<Error>),
 }
 
 impl Stmt {
@@ -29,9 +30,28 @@ impl Stmt {
             Stmt::FunctionStmt(stmt) => visitor.visit_function_stmt(stmt.clone()),
             Stmt::ReturnStmt(stmt) => visitor.visit_return_stmt(stmt),
             Stmt::ClassStmt(stmt) => visitor.visit_class_stmt(stmt.clone()),
+            Stmt::ErrorStmt(stmt) => visitor.visit_error_stmt(stmt),
         }
     }
 }
+impl Stmt {
+    /// The first token that locates the statement, if it has one.
+    pub(crate) fn token(&self) -> Option<&Token> {
+        match self {
+            Stmt::ExpressionStmt(stmt) => stmt.expression.token(),
+            Stmt::PrintStmt(stmt) => Some(&stmt.keyword),
+            Stmt::VarStmt(stmt) => Some(&stmt.name),
+            Stmt::BlockStmt(stmt) => stmt.statements.iter().find_map(Stmt::token),
+            Stmt::IfStmt(stmt) => Some(&stmt.keyword),
+            Stmt::WhileStmt(stmt) => Some(&stmt.keyword),
+            Stmt::FunctionStmt(stmt) => Some(&stmt.name),
+            Stmt::ReturnStmt(stmt) => Some(&stmt.keyword),
+            Stmt::ClassStmt(stmt) => Some(&stmt.name),
+            Stmt::ErrorStmt(stmt) => stmt.tokens.first(),
+        }
+    }
+}
+
 pub(crate) trait Visitor<R> {
     fn visit_expression_stmt(&mut self, stmt: &Expression) -> R;
     fn visit_print_stmt(&mut self, stmt: &Print) -> R;
@@ -42,6 +62,7 @@ pub(crate) trait Visitor<R> {
     fn visit_function_stmt(&mut self, stmt: Rc<Function>) -> R;
     fn visit_return_stmt(&mut self, stmt: &Return) -> R;
     fn visit_class_stmt(&mut self, stmt: Rc<Class>) -> R;
+    fn visit_error_stmt(&mut self, stmt: &Error) -> R;
 }
 
 pub(crate) struct Expression {
@@ -55,12 +76,16 @@ impl Expression {
 }
 
 pub(crate) struct Print {
+    pub(crate) keyword: Token,
     pub(crate) expression: Expr,
 }
 
 impl Print {
-    pub(crate) fn new(expression: Expr) -> Box<Self> {
-        Box::new(Self { expression })
+    pub(crate) fn new(keyword: Token, expression: Expr) -> Box<Self> {
+        Box::new(Self {
+            keyword,
+            expression,
+        })
     }
 }
 
@@ -89,14 +114,21 @@ impl Block {
 }
 
 pub(crate) struct If {
+    pub(crate) keyword: Token,
     pub(crate) condition: Expr,
     pub(crate) then_branch: Stmt,
     pub(crate) else_branch: Option<Stmt>,
 }
 
 impl If {
-    pub(crate) fn new(condition: Expr, then_branch: Stmt, else_branch: Option<Stmt>) -> Box<Self> {
+    pub(crate) fn new(
+        keyword: Token,
+        condition: Expr,
+        then_branch: Stmt,
+        else_branch: Option<Stmt>,
+    ) -> Box<Self> {
         Box::new(Self {
+            keyword,
             condition,
             then_branch,
             else_branch,
@@ -104,14 +136,21 @@ impl If {
     }
 }
 
+/// A `while` loop, or the loop a `for` statement turns into; `keyword` is
+/// whichever of the two the source used.
 pub(crate) struct While {
+    pub(crate) keyword: Token,
     pub(crate) condition: Expr,
     pub(crate) body: Stmt,
 }
 
 impl While {
-    pub(crate) fn new(condition: Expr, body: Stmt) -> Box<Self> {
-        Box::new(Self { condition, body })
+    pub(crate) fn new(keyword: Token, condition: Expr, body: Stmt) -> Box<Self> {
+        Box::new(Self {
+            keyword,
+            condition,
+            body,
+        })
     }
 }
 
@@ -157,3 +196,16 @@ impl Class {
         })
     }
 }
+
+/// A declaration that failed to parse, standing in for it so the rest of the
+/// file can still be analysed.
+pub(crate) struct Error {
+    /// The tokens the parser skipped to recover, starting where the declaration did.
+    pub(crate) tokens: Vec<Token>,
+}
+
+impl Error {
+    pub(crate) fn new(tokens: Vec<Token>) -> Box<Self> {
+        Box::new(Self { tokens })
+    }
+}
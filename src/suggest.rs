@@ -0,0 +1,72 @@
+/// Points out a candidate that `name` is probably a typo of, for the help of
+/// an "Undefined ..." error.
+pub(crate) fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<Box<str>> {
+    closest(name, candidates).map(|candidate| format!("Did you mean '{}'?", candidate).into())
+}
+
+/// The candidate with the fewest edits from `name`, if it takes at most one
+/// edit per three characters, so very short names get no suggestion. Ties go
+/// to the alphabetically first candidate.
+fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = name.chars().count() / 3;
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance > 0 && *distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance where swapping two adjacent characters counts as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + substitution);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lox, LoxError};
+
+    #[test]
+    fn suggestion_is_help_and_not_part_of_the_message() {
+        let mut lox = Lox::with_output(std::io::sink());
+        let error = lox.eval("var abcd = 1;\nprint abce;").unwrap_err();
+        let LoxError::Runtime(diagnostic) = &error else {
+            panic!("expected a runtime error");
+        };
+        assert_eq!(diagnostic.message, "Undefined variable 'abce'.");
+        assert_eq!(diagnostic.help.as_deref(), Some("Did you mean 'abcd'?"));
+        assert_eq!(error.to_string(), "Undefined variable 'abce'.\n[line 2]");
+    }
+
+    #[test]
+    fn short_names_get_no_suggestion() {
+        assert_eq!(did_you_mean("ab", ["ac"]), None);
+        assert_eq!(
+            did_you_mean("count", ["cuont", "mount"]).as_deref(),
+            Some("Did you mean 'cuont'?")
+        );
+    }
+}
@@ -1,8 +1,11 @@
 use std::{cell::RefCell, rc::Rc};
 
-use crate::{lox_callable::LoxCallable, lox_instance::LoxInstance, token_type::TokenType};
+use crate::{
+    lox_callable::LoxCallable, lox_instance::LoxInstance, span::Span, token_type::TokenType,
+};
+/// A Lox value.
 #[derive(Clone)]
-pub(crate) enum Literal {
+pub enum Literal {
     Float(f64),
     StringLiteral(String),
     BoolLiteral(bool),
@@ -11,12 +14,13 @@ pub(crate) enum Literal {
     Instance(Rc<RefCell<LoxInstance>>),
 }
 impl Literal {
-    pub(crate) fn stringify(&self) -> String {
+    /// Formats the value the way `print` does.
+    pub fn stringify(&self) -> String {
         match self {
             Literal::NoneLiteral => String::from("nil"),
             Literal::BoolLiteral(b) => format!("{}", b),
             Literal::Float(f) => {
-                if f.fract() == 0.0 {
+                if f.fract() == 0.0 && f.abs() <= i32::MAX as f64 {
                     format!("{}", *f as i32)
                 } else {
                     format!("{}", f)
@@ -27,6 +31,19 @@ impl Literal {
             Literal::Instance(inst) => (*inst).borrow().stringify(),
         }
     }
+    /// The name scripts and error messages use for the value's type.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Literal::NoneLiteral => "nil",
+            Literal::BoolLiteral(_) => "boolean",
+            Literal::Float(_) => "number",
+            Literal::StringLiteral(_) => "string",
+            Literal::Callable(LoxCallable::UserClass(_)) => "class",
+            Literal::Callable(_) => "function",
+            Literal::Instance(_) => "instance",
+        }
+    }
+
     pub(crate) fn is_truthy(&self) -> bool {
         match self {
             Self::NoneLiteral => false,
@@ -56,7 +73,7 @@ impl Literal {
                 return sself == sother;
             }
         }
-        return false;
+        false
     }
 
     pub(crate) fn negate_and_wrap(&self) -> Self {
@@ -72,7 +89,7 @@ impl Literal {
 
     pub(crate) fn unwrap_str_literal(&self) -> &str {
         match self {
-            Self::StringLiteral(str_literal) => &str_literal,
+            Self::StringLiteral(str_literal) => str_literal,
             _ => panic!(),
         }
     }
@@ -90,17 +107,11 @@ impl Literal {
     }
 
     pub(crate) fn is_float(&self) -> bool {
-        match self {
-            Self::Float(_) => true,
-            _ => false,
-        }
+        matches!(self, Self::Float(_))
     }
 
     pub(crate) fn is_string(&self) -> bool {
-        match self {
-            Self::StringLiteral(_) => true,
-            _ => false,
-        }
+        matches!(self, Self::StringLiteral(_))
     }
 }
 
@@ -109,7 +120,7 @@ pub(crate) struct Token {
     pub(crate) token_type: TokenType,
     pub(crate) lexeme: String,
     pub(crate) literal: Literal,
-    pub(crate) line: usize,
+    pub(crate) span: Span,
 }
 
 impl Token {
@@ -117,13 +128,13 @@ impl Token {
         token_type: TokenType,
         lexeme: String,
         literal: Literal,
-        line: usize,
+        span: Span,
     ) -> Self {
         Token {
             token_type,
             lexeme,
             literal,
-            line,
+            span,
         }
     }
 }
@@ -1,5 +1,6 @@
-#[derive(Copy, Clone, PartialEq, Debug)]
-pub(crate) enum TokenType {
+/// The kind of a token.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TokenType {
     // Single-character tokens.
     LeftParen,
     RightParen,
@@ -26,6 +27,8 @@ pub(crate) enum TokenType {
     // Literals.
     Identifier,
     StringToken,
+    // The part of a string up to a `${`.
+    StringInterpolation,
     Number,
 
     // Keywords.
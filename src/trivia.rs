@@ -0,0 +1,174 @@
+use std::fmt;
+
+use crate::{
+    span::{FileId, Span},
+    token::Token,
+    token_type::TokenType,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// Spaces and tabs, and a byte order mark at the start of the file.
+    Whitespace,
+    /// `\n` or `\r\n`.
+    Newline,
+    LineComment,
+    BlockComment,
+    /// Text the scanner couldn't make a token of, such as an unexpected
+    /// character or an unterminated string.
+    Skipped,
+}
+
+/// Source text between tokens that doesn't change what the program means.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
+
+/// A token together with the trivia around it, from [`crate::Lox::tokenize`].
+///
+/// A token's trailing trivia runs up to the end of its line; the newline and
+/// everything after it lead the next token. Writing out every token with
+/// [`fmt::Display`] reproduces the source exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken {
+    pub token_type: TokenType,
+    /// The token's own text, which is empty for the final `Eof`.
+    pub text: String,
+    pub span: Span,
+    pub leading_trivia: Vec<Trivia>,
+    pub trailing_trivia: Vec<Trivia>,
+}
+
+impl fmt::Display for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for trivia in &self.leading_trivia {
+            write!(f, "{}", trivia.text)?;
+        }
+        write!(f, "{}", self.text)?;
+        for trivia in &self.trailing_trivia {
+            write!(f, "{}", trivia.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Hands the trivia the scanner kept to the tokens around it, and keeps any
+/// text that is in neither as [`TriviaKind::Skipped`].
+pub(crate) fn attach(
+    source: &str,
+    file: FileId,
+    tokens: Vec<Token>,
+    trivia: Vec<Trivia>,
+) -> Vec<SyntaxToken> {
+    let mut trivia = trivia.into_iter().peekable();
+    let mut position = 0;
+    let mut syntax_tokens: Vec<SyntaxToken> = Vec::new();
+    for token in tokens {
+        let start = token.span.start as usize;
+        let mut between = Vec::new();
+        while let Some(next) = trivia.next_if(|next| (next.span.start as usize) < start) {
+            skipped(
+                &mut between,
+                source,
+                file,
+                position,
+                next.span.start as usize,
+            );
+            position = next.span.end as usize;
+            between.push(next);
+        }
+        skipped(&mut between, source, file, position, start);
+        position = token.span.end as usize;
+
+        let mut leading = between.into_iter().peekable();
+        if let Some(previous) = syntax_tokens.last_mut() {
+            while let Some(next) = leading.next_if(|next| next.kind != TriviaKind::Newline) {
+                previous.trailing_trivia.push(next);
+            }
+        }
+        syntax_tokens.push(SyntaxToken {
+            token_type: token.token_type,
+            text: token.lexeme,
+            span: token.span,
+            leading_trivia: leading.collect(),
+            trailing_trivia: Vec::new(),
+        });
+    }
+    syntax_tokens
+}
+
+fn skipped(trivia: &mut Vec<Trivia>, source: &str, file: FileId, start: usize, end: usize) {
+    if start >= end {
+        return;
+    }
+    let before = &source[..start];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    trivia.push(Trivia {
+        kind: TriviaKind::Skipped,
+        text: source[start..end].to_string(),
+        span: Span {
+            file,
+            start: start as u32,
+            end: end as u32,
+            line: before.matches('\n').count() as u32 + 1,
+            column: before[line_start..].chars().count() as u32 + 1,
+        },
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lox;
+
+    fn round_trip(source: &str) -> Vec<SyntaxToken> {
+        let (tokens, _) = Lox::new().tokenize("test.lox", source);
+        let text: String = tokens.iter().map(|token| token.to_string()).collect();
+        assert_eq!(text, source);
+        tokens
+    }
+
+    #[test]
+    fn tokens_reproduce_the_source() {
+        round_trip("");
+        round_trip("\u{feff}print 1; // one\n");
+        round_trip("var a = 1;\r\nprint a; // a\r\n\r\n");
+        round_trip("/* outer /* inner */ still */ print 1;\n");
+        round_trip("print \"a${ \"b ${1} c\" }d\";\n");
+        round_trip("var café = \"\\q\";\tprint café;");
+        round_trip("print \"unterminated;\nprint 1;");
+        round_trip("print 1 @ # 2;\n");
+    }
+
+    #[test]
+    fn trailing_trivia_stops_at_the_newline() {
+        let tokens = round_trip("a; // note\r\n  b;");
+        let semicolon = &tokens[1];
+        let kinds: Vec<_> = semicolon.trailing_trivia.iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, [TriviaKind::Whitespace, TriviaKind::LineComment]);
+        assert_eq!(semicolon.trailing_trivia[1].text, "// note");
+        let kinds: Vec<_> = tokens[2].leading_trivia.iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, [TriviaKind::Newline, TriviaKind::Whitespace]);
+        assert_eq!(tokens[2].leading_trivia[0].text, "\r\n");
+    }
+
+    #[test]
+    fn unscannable_text_is_skipped() {
+        let tokens = round_trip("\"abc");
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_type, TokenType::Eof);
+        let kinds: Vec<_> = tokens[0].leading_trivia.iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, [TriviaKind::Skipped]);
+    }
+
+    #[test]
+    fn byte_order_mark_leads_the_first_token() {
+        let tokens = round_trip("\u{feff}x");
+        assert_eq!(tokens[0].leading_trivia[0].kind, TriviaKind::Whitespace);
+        assert_eq!(tokens[0].leading_trivia[0].text, "\u{feff}");
+        assert_eq!(tokens[0].text, "x");
+    }
+}